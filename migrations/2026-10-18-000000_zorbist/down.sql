@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE zorbist_state;
+
+DROP TABLE evaluation;
+
+DROP TABLE aspect;
+
+DROP TABLE zorbist;
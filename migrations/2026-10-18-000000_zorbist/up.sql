@@ -0,0 +1,31 @@
+-- 开局库：局面(zorbist) -> 起点(aspect) -> 终点及次数(evaluation)
+
+CREATE TABLE zorbist (
+    id BIGINT PRIMARY KEY NOT NULL,
+    lock BIGINT NOT NULL
+);
+
+CREATE TABLE aspect (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, 
+    from_index INTEGER NOT NULL, 
+
+    zorbist_id BIGINT NOT NULL, 
+    FOREIGN KEY (zorbist_id) REFERENCES zorbist(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    UNIQUE (zorbist_id, from_index)
+);
+
+CREATE TABLE evaluation (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, 
+    to_index INTEGER NOT NULL, 
+    count INTEGER NOT NULL,
+
+    aspect_id INTEGER NOT NULL, 
+    FOREIGN KEY (aspect_id) REFERENCES aspect(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    UNIQUE (aspect_id, to_index)
+);
+
+-- 已计入开局库的manual最大id，用于增量更新
+CREATE TABLE zorbist_state (
+    id INTEGER PRIMARY KEY NOT NULL, 
+    manual_id INTEGER NOT NULL
+);
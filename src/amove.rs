@@ -1,17 +1,16 @@
 #![allow(dead_code)]
 
+use crate::bit_board;
 use crate::board;
 use crate::coord;
-use std::cell::RefCell;
+use crate::piece;
+use serde_derive::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::rc::Rc;
-use std::rc::Weak;
-// use serde_derive::Deserialize;
-// use serde_derive::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
 // use crate::piece;
 // use std::arch::x86_64::_CMP_FALSE_OQ;
 // use std::borrow::BorrowMut;
-// use std::sync::{Rc, RwLock, Weak};
 // use crate::coord::CoordPair;
 // use std::borrow::BorrowMut;
 // use crate::piece;
@@ -20,45 +19,73 @@ use std::rc::Weak;
 // use std::borrow::Borrow;
 // use crate::common;
 
-#[derive(Debug)] //, Serialize, Deserialize
+#[derive(Debug)]
 pub struct Move {
-    before: Option<Weak<Move>>,
-    after: RefCell<Option<Vec<Rc<Move>>>>,
+    before: RwLock<Option<Weak<Move>>>,
+    after: RwLock<Option<Vec<Arc<Move>>>>,
 
     pub coordpair: coord::CoordPair,
-    remark: RefCell<Option<String>>,
+    remark: RwLock<Option<String>>,
+    // 经过本着的棋局数，合并棋谱时累加
+    count: AtomicUsize,
+    // 已从着法树中删除，其前着为None但不是根着法
+    detached: AtomicBool,
+}
+
+// 着法树的平面(序号)表示，供序列化及跨线程传递：
+// 按前序排列，首个为根着法，before为前着在序列中的序号
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MoveNode {
+    pub before: usize,
+    pub coordpair: coord::CoordPair,
+    pub remark: Option<String>,
+    // 早先序列化的数据无此字段，取1
+    #[serde(default = "default_count")]
+    pub count: usize,
+}
+
+fn default_count() -> usize {
+    1
 }
 
 impl Move {
-    pub fn root() -> Rc<Self> {
-        Rc::new(Move {
-            before: None,
-            after: RefCell::new(None),
+    pub fn root() -> Arc<Self> {
+        Arc::new(Move {
+            before: RwLock::new(None),
+            after: RwLock::new(None),
             coordpair: coord::CoordPair::new(),
-            remark: RefCell::new(None),
+            remark: RwLock::new(None),
+            count: AtomicUsize::new(1),
+            detached: AtomicBool::new(false),
         })
     }
 
     pub fn is_root(&self) -> bool {
-        self.before.is_none()
+        self.before.read().unwrap().is_none() && !self.is_detached()
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.detached.load(Ordering::Relaxed)
+    }
+
+    fn detach(&self) {
+        *self.before.write().unwrap() = None;
+        self.detached.store(true, Ordering::Relaxed);
     }
 
     pub fn after_len(&self) -> usize {
-        match &*self.after.borrow() {
+        match &*self.after.read().unwrap() {
             Some(after) => after.len(),
             None => 0,
         }
     }
 
-    pub fn after(&self) -> Option<Vec<Rc<Self>>> {
-        match &*self.after.borrow() {
-            Some(after) => Some(after.clone()),
-            None => None,
-        }
+    pub fn after(&self) -> Option<Vec<Arc<Self>>> {
+        self.after.read().unwrap().as_ref().cloned()
     }
 
     pub fn remark(&self) -> String {
-        match &*self.remark.borrow() {
+        match &*self.remark.read().unwrap() {
             Some(remark) => remark.clone(),
             None => String::new(),
         }
@@ -72,51 +99,278 @@ impl Move {
     }
 
     pub fn set_remark(&self, remark: String) {
-        *self.remark.borrow_mut() = Self::converted_remark(remark);
+        *self.remark.write().unwrap() = Self::converted_remark(remark);
     }
 
-    pub fn append(self: &Rc<Move>, coordpair: coord::CoordPair, remark: String) -> Rc<Self> {
-        let amove = Rc::new(Self {
-            before: Some(Rc::downgrade(self)),
-            after: RefCell::new(None),
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn add_count(&self, count: usize) {
+        self.count.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn append(self: &Arc<Move>, coordpair: coord::CoordPair, remark: String) -> Arc<Self> {
+        let amove = Arc::new(Self {
+            before: RwLock::new(Some(Arc::downgrade(self))),
+            after: RwLock::new(None),
 
             coordpair,
-            remark: RefCell::new(Self::converted_remark(remark)),
+            remark: RwLock::new(Self::converted_remark(remark)),
+            count: AtomicUsize::new(1),
+            detached: AtomicBool::new(false),
         });
 
         self.after
-            .borrow_mut()
+            .write()
+            .unwrap()
             .get_or_insert(vec![])
             .push(amove.clone());
 
         amove
     }
 
-    pub fn before(&self) -> Option<Rc<Self>> {
-        match &self.before {
-            Some(before) => Some(before.upgrade().unwrap()),
-            None => None,
+    pub fn before(&self) -> Option<Arc<Self>> {
+        self.before
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|before| before.upgrade().unwrap())
+    }
+
+    // 在前着的后续着法中的序号，根着法为None
+    pub fn sibling_index(self: &Arc<Self>) -> Option<(Arc<Self>, usize)> {
+        let before = self.before()?;
+        let index = before
+            .after
+            .read()
+            .unwrap()
+            .as_ref()?
+            .iter()
+            .position(|amove| Arc::ptr_eq(amove, self))?;
+
+        Some((before, index))
+    }
+
+    // 从前着的后续着法中删除本着及其后全部着法
+    pub fn delete(self: &Arc<Self>) -> bool {
+        match self.sibling_index() {
+            Some((before, index)) => {
+                let mut after = before.after.write().unwrap();
+                let after_moves = after.as_mut().unwrap();
+                after_moves.remove(index);
+                if after_moves.is_empty() {
+                    *after = None;
+                }
+                self.detach();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 本着移至同级着法的index位置，index为0即成为主线
+    pub fn reorder(self: &Arc<Self>, index: usize) -> bool {
+        match self.sibling_index() {
+            Some((before, old_index)) => {
+                let mut after = before.after.write().unwrap();
+                let after_moves = after.as_mut().unwrap();
+                if index >= after_moves.len() {
+                    return false;
+                }
+
+                let amove = after_moves.remove(old_index);
+                after_moves.insert(index, amove);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn promote(self: &Arc<Self>) -> bool {
+        self.reorder(0)
+    }
+
+    // 删除本着之后的全部着法
+    pub fn truncate(&self) {
+        for amove in self.after.write().unwrap().take().unwrap_or_default() {
+            amove.detach();
         }
     }
 
-    pub fn before_moves(self: &Rc<Self>, contains_self: bool) -> Vec<Rc<Self>> {
+    // 在本着与其后续着法之间插入若干半回合，原后续着法移至末个插入着法之后并重新检验，
+    // 走棋方不符或不合法的即删除。board为根着法时的局面，first_color为首着的走棋方，
+    // 插入的着法为空或不合法时返回None，否则返回首个插入着法
+    pub fn insert(
+        self: &Arc<Self>,
+        coordpairs: &[coord::CoordPair],
+        board: &board::Board,
+        first_color: piece::Color,
+    ) -> Option<Arc<Self>> {
+        if coordpairs.is_empty() {
+            return None;
+        }
+
+        let mut bit_board = board.to_move(self, true).bit_board();
+        let mut color = self.after_color(first_color);
+        for coordpair in coordpairs {
+            let (from_index, to_index) = coordpair.from_to_index();
+            if !bit_board
+                .legal_from_to_indexs(color)
+                .contains(&(from_index, to_index))
+            {
+                return None;
+            }
+
+            bit_board.do_move(from_index, to_index);
+            color = piece::other_color(color);
+        }
+
+        let after = self.after.write().unwrap().take();
+        let first_move = self.append(coordpairs[0], String::new());
+        let mut amove = first_move.clone();
+        for coordpair in &coordpairs[1..] {
+            amove = amove.append(*coordpair, String::new());
+        }
+        if let Some(after) = after {
+            for bmove in &after {
+                *bmove.before.write().unwrap() = Some(Arc::downgrade(&amove));
+            }
+            *amove.after.write().unwrap() = Some(after);
+        }
+        amove.retain_valid(board, first_color);
+
+        Some(first_move)
+    }
+
+    // 删除不合走棋方规则的后续着法及其后全部着法，返回删除的着法数(不含其后着法)
+    pub fn retain_valid(
+        self: &Arc<Self>,
+        board: &board::Board,
+        first_color: piece::Color,
+    ) -> usize {
+        fn retain_after(amove: &Arc<Move>, bit_board: &mut bit_board::BitBoard) -> usize {
+            let mut count = 0;
+            let legal_from_to_indexs = bit_board.legal_from_to_indexs(bit_board.side_color());
+            for bmove in amove.after().unwrap_or_default() {
+                let (from_index, to_index) = bmove.coordpair.from_to_index();
+                if legal_from_to_indexs.contains(&(from_index, to_index)) {
+                    let eat_kind = bit_board.do_move(from_index, to_index).unwrap();
+                    count += retain_after(&bmove, bit_board);
+                    bit_board.undo_move(from_index, to_index, eat_kind);
+                } else {
+                    bmove.delete();
+                    count += 1;
+                }
+            }
+
+            count
+        }
+
+        let mut bit_board = board.to_move(self, true).bit_board();
+        bit_board.set_side_color(self.after_color(first_color));
+        retain_after(self, &mut bit_board)
+    }
+
+    pub fn to_nodes(self: &Arc<Self>) -> Vec<MoveNode> {
+        let mut nodes = Vec::new();
+        let mut move_stack = vec![(self.clone(), 0)];
+        while let Some((amove, before)) = move_stack.pop() {
+            let index = nodes.len();
+            nodes.push(MoveNode {
+                before,
+                coordpair: amove.coordpair,
+                remark: amove.remark.read().unwrap().clone(),
+                count: amove.count(),
+            });
+
+            for bmove in amove.after().unwrap_or_default().into_iter().rev() {
+                move_stack.push((bmove, index));
+            }
+        }
+
+        nodes
+    }
+
+    // 序列为空或前着序号不小于本着序号时返回None
+    pub fn from_nodes(nodes: &[MoveNode]) -> Option<Arc<Self>> {
+        let root_move = Self::root();
+        let root_node = nodes.first()?;
+        root_move.set_remark(root_node.remark.clone().unwrap_or_default());
+        root_move.count.store(root_node.count, Ordering::Relaxed);
+        let mut moves = vec![root_move];
+        for (index, node) in nodes.iter().enumerate().skip(1) {
+            if node.before >= index {
+                return None;
+            }
+
+            let amove =
+                moves[node.before].append(node.coordpair, node.remark.clone().unwrap_or_default());
+            amove.count.store(node.count, Ordering::Relaxed);
+            moves.push(amove);
+        }
+
+        Some(moves.swap_remove(0))
+    }
+
+    // 将other的后续着法并入本着：相同的着法合为一支，累加棋局数并逐行保留不同的注解，
+    // 不同的着法成为变着
+    pub fn merge(self: &Arc<Self>, other: &Arc<Self>) {
+        self.add_count(other.count());
+        let other_remark = other.remark();
+        let remark = self.remark();
+        if remark.is_empty() {
+            self.set_remark(other_remark);
+        } else if !other_remark.is_empty()
+            && !format!("\n{remark}\n").contains(&format!("\n{other_remark}\n"))
+        {
+            self.set_remark(format!("{remark}\n{other_remark}"));
+        }
+
+        for other_move in other.after().unwrap_or_default() {
+            let amove = self
+                .after()
+                .unwrap_or_default()
+                .into_iter()
+                .find(|amove| amove.coordpair == other_move.coordpair);
+            match amove {
+                Some(amove) => amove.merge(&other_move),
+                None => {
+                    let amove = self.append(other_move.coordpair, String::new());
+                    amove.count.store(0, Ordering::Relaxed);
+                    amove.merge(&other_move);
+                }
+            }
+        }
+    }
+
+    pub fn before_moves(self: &Arc<Self>, contains_self: bool) -> Vec<Arc<Self>> {
         let mut before_moves = Vec::new();
         let mut amove = if contains_self {
             self.clone()
         } else {
             self.before().unwrap()
         };
-        while !amove.is_root() {
-            before_moves.insert(0, amove.clone());
-            amove = amove.before().unwrap();
+        while let Some(before) = amove.before() {
+            before_moves.insert(0, amove);
+            amove = before;
         }
         // before_moves.reverse();
 
         before_moves
     }
 
-    pub fn get_all_after_moves(self: &Rc<Self>) -> Vec<Rc<Self>> {
-        fn enqueue_after(move_deque: &mut VecDeque<Rc<Move>>, amove: &Rc<Move>) {
+    // 本着之后的走棋方，first_color为首着的走棋方
+    pub fn after_color(self: &Arc<Self>, first_color: piece::Color) -> piece::Color {
+        if self.before_moves(true).len().is_multiple_of(2) {
+            first_color
+        } else {
+            piece::other_color(first_color)
+        }
+    }
+
+    pub fn get_all_after_moves(self: &Arc<Self>) -> Vec<Arc<Self>> {
+        fn enqueue_after(move_deque: &mut VecDeque<Arc<Move>>, amove: &Arc<Move>) {
             if let Some(after) = amove.after() {
                 for bmove in after {
                     move_deque.push_back(bmove);
@@ -136,27 +390,25 @@ impl Move {
     }
 
     pub fn to_string(
-        self: &Rc<Self>,
+        self: &Arc<Self>,
         record_type: coord::RecordType,
         board: &board::Board,
     ) -> String {
         let coordpair_string = if self.is_root() {
             String::new()
         } else {
-            if record_type == coord::RecordType::PgnZh {
-                if self.is_root() {
-                    String::new()
-                } else {
-                    board
-                        .to_move(self, false)
-                        .get_zhstr_from_coordpair(&self.coordpair)
-                }
-            } else {
-                self.coordpair.to_string(record_type)
+            match record_type {
+                coord::RecordType::PgnZh => board
+                    .to_move(self, false)
+                    .get_zhstr_from_coordpair(&self.coordpair),
+                coord::RecordType::PgnWxf => board
+                    .to_move(self, false)
+                    .get_wxfstr_from_coordpair(&self.coordpair),
+                _ => self.coordpair.to_string(record_type),
             }
         };
 
-        let remark = match &*self.remark.borrow() {
+        let remark = match &*self.remark.read().unwrap() {
             Some(remark) => format!("{{{}}}", remark),
             None => String::new(),
         };
@@ -192,4 +444,107 @@ mod tests {
             amove.to_string(coord::RecordType::Txt, &board)
         );
     }
+
+    #[test]
+    fn test_edit() {
+        let board = board::Board::new();
+        let coordpair = |frow, fcol, trow, tcol| {
+            coord::CoordPair::from_row_col(frow, fcol, trow, tcol).unwrap()
+        };
+        // 炮二平五 马８进７(马２进３) 马二进三
+        let root_move = Move::root();
+        let amove = root_move.append(coordpair(7, 7, 7, 4), String::new());
+        let bmove = amove.append(coordpair(0, 7, 2, 6), String::new());
+        let other_move = amove.append(coordpair(0, 1, 2, 2), String::new());
+        let cmove = bmove.append(coordpair(9, 7, 7, 6), String::new());
+
+        assert!(other_move.promote());
+        assert!(Arc::ptr_eq(&amove.after().unwrap()[0], &other_move));
+        assert!(!other_move.reorder(2));
+        assert!(other_move.reorder(1));
+        assert!(Arc::ptr_eq(&amove.after().unwrap()[0], &bmove));
+        assert!(!root_move.promote());
+
+        // 只插入兵七进一，原后续着法炮二平五成为红方连走，予以删除
+        let red = piece::Color::Red;
+        let other_root_move = Move::root();
+        let old_move = other_root_move.append(coordpair(7, 7, 7, 4), String::new());
+        old_move.append(coordpair(0, 7, 2, 6), String::new());
+        let insert_move = other_root_move
+            .insert(&[coordpair(6, 6, 5, 6)], &board, red)
+            .unwrap();
+        assert_eq!(other_root_move.get_all_after_moves().len(), 1);
+        assert_eq!(insert_move.after_len(), 0);
+        assert!(old_move.is_detached());
+        assert!(root_move.insert(&[], &board, red).is_none());
+        assert!(root_move
+            .insert(&[coordpair(6, 6, 5, 6), coordpair(7, 7, 7, 4)], &board, red)
+            .is_none());
+
+        // 插入兵七进一 卒７进１，其后着法仍然合法
+        let insert_move = root_move
+            .insert(&[coordpair(6, 6, 5, 6), coordpair(3, 6, 4, 6)], &board, red)
+            .unwrap();
+        let insert_end_move = insert_move.after().unwrap()[0].clone();
+        assert!(Arc::ptr_eq(&amove.before().unwrap(), &insert_end_move));
+        assert!(Arc::ptr_eq(&insert_move.before().unwrap(), &root_move));
+        assert_eq!(cmove.before_moves(true).len(), 5);
+        assert_eq!(root_move.get_all_after_moves().len(), 6);
+
+        // 插入炮二平五 马８进７后，原后续着法炮二平五不再合法
+        let other_root_move = Move::root();
+        let old_move = other_root_move.append(coordpair(7, 7, 7, 4), String::new());
+        old_move.append(coordpair(0, 7, 2, 6), String::new());
+        other_root_move
+            .insert(&[coordpair(7, 7, 7, 4), coordpair(0, 7, 2, 6)], &board, red)
+            .unwrap();
+        assert_eq!(other_root_move.get_all_after_moves().len(), 2);
+        assert!(!old_move.is_root() && old_move.is_detached());
+
+        // 黑方走棋的局面：红方着法不合法
+        let other_root_move = Move::root();
+        other_root_move.append(coordpair(7, 7, 7, 4), String::new());
+        assert_eq!(other_root_move.retain_valid(&board, piece::Color::Black), 1);
+        assert_eq!(other_root_move.after_len(), 0);
+
+        assert!(other_move.delete());
+        assert!(!other_move.is_root() && other_move.is_detached());
+        assert!(other_move.before().is_none());
+        assert!(other_move.before_moves(true).is_empty());
+        assert!(!other_move.delete());
+        assert_eq!(amove.after_len(), 1);
+        bmove.truncate();
+        assert_eq!(bmove.after_len(), 0);
+        assert!(!cmove.is_root() && cmove.before().is_none());
+        assert!(!root_move.is_detached());
+    }
+
+    #[test]
+    fn test_nodes() {
+        let coordpair = |frow, fcol, trow, tcol| {
+            coord::CoordPair::from_row_col(frow, fcol, trow, tcol).unwrap()
+        };
+        let root_move = Move::root();
+        let amove = root_move.append(coordpair(7, 7, 7, 4), String::from("中炮"));
+        amove.append(coordpair(0, 7, 2, 6), String::new());
+        amove.append(coordpair(0, 1, 2, 2), String::new());
+        root_move.append(coordpair(6, 2, 5, 2), String::new());
+
+        let nodes = root_move.to_nodes();
+        let befores: Vec<usize> = nodes.iter().map(|node| node.before).collect();
+        assert_eq!(befores, vec![0, 0, 1, 1, 0]);
+        assert_eq!(nodes[1].remark.as_deref(), Some("中炮"));
+
+        let nodes_move = Move::from_nodes(&nodes).unwrap();
+        assert_eq!(nodes_move.to_nodes(), nodes);
+        assert_eq!(nodes_move.after().unwrap()[0].after_len(), 2);
+        assert!(Move::from_nodes(&nodes[1..]).is_none());
+
+        let node: MoveNode = serde_json::from_str(
+            r#"{"before":0,"coordpair":{"from_coord":{"row":7,"col":7},"to_coord":{"row":7,"col":4}},"remark":null}"#,
+        )
+        .unwrap();
+        assert_eq!(node.count, 1);
+        assert!(Move::from_nodes(&[]).is_none());
+    }
 }
@@ -5,21 +5,33 @@ use crate::amove;
 use crate::bit_constant;
 use crate::board;
 use crate::coord::{
-    self, COLCOUNT, COLSTATECOUNT, LEGSTATECOUNT, ROWCOUNT, ROWSTATECOUNT, SEATCOUNT, SIDECOUNT,
+    self, CoordPair, COLCOUNT, COLSTATECOUNT, LEGSTATECOUNT, ROWCOUNT, ROWSTATECOUNT, SEATCOUNT,
+    SIDECOUNT,
 };
 use crate::evaluation::*;
 use crate::manual;
 use crate::manual_move;
 use crate::models;
 use crate::piece::{self, COLORCOUNT, KINDCOUNT};
-use std::rc::Rc;
+use std::sync::Arc;
 
 type GetEvaluation =
     fn(&BitBoard, from_to_index: (usize, usize), eat_kind: piece::Kind) -> Evaluation;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameState {
+    Ongoing,
+    InCheck,
+    Checkmated,
+    // 困毙(无子可走)，在象棋中判负
+    Stalemated,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct BitBoard {
     bottom_color: piece::Color,
+    // 当前走棋方(走棋后交换)
+    side_color: piece::Color,
 
     // 计算中间存储数据(基本局面改动时更新)
     bit_pieces: [[bit_constant::BitAtom; KINDCOUNT]; COLORCOUNT],
@@ -37,6 +49,7 @@ impl BitBoard {
     pub fn from(pieces: &board::Pieces) -> Self {
         let mut bit_board = Self {
             bottom_color: board::get_bottom_color(pieces),
+            side_color: piece::Color::Red,
             bit_pieces: [[0; KINDCOUNT]; COLORCOUNT],
 
             key: 0,
@@ -58,7 +71,19 @@ impl BitBoard {
         bit_board
     }
 
-    fn get_color(&self, index: usize) -> Option<piece::Color> {
+    pub fn bottom_color(&self) -> piece::Color {
+        self.bottom_color
+    }
+
+    pub fn side_color(&self) -> piece::Color {
+        self.side_color
+    }
+
+    pub fn set_side_color(&mut self, color: piece::Color) {
+        self.side_color = color;
+    }
+
+    pub fn get_color(&self, index: usize) -> Option<piece::Color> {
         let index_mask = bit_constant::MASK[index];
         if self.color_pieces(piece::Color::Red) & index_mask != 0 {
             Some(piece::Color::Red)
@@ -69,7 +94,7 @@ impl BitBoard {
         }
     }
 
-    fn get_kind(&self, index: usize) -> piece::Kind {
+    pub fn get_kind(&self, index: usize) -> piece::Kind {
         let index_mask = bit_constant::MASK[index];
         for kind in [
             piece::Kind::Rook,
@@ -90,6 +115,10 @@ impl BitBoard {
         piece::Kind::NoKind
     }
 
+    pub fn get_indexs_from_color_kind(&self, color: piece::Color, kind: piece::Kind) -> Vec<usize> {
+        bit_constant::get_indexs_from_bitatom(self.bit_pieces[color as usize][kind as usize])
+    }
+
     fn color_pieces(&self, color: piece::Color) -> bit_constant::BitAtom {
         let mut result = 0;
         for kind_piece in self.bit_pieces[color as usize] {
@@ -112,7 +141,7 @@ impl BitBoard {
         result
     }
 
-    fn get_move_from_index(&self, index: usize) -> bit_constant::BitAtom {
+    pub fn get_move_from_index(&self, index: usize) -> bit_constant::BitAtom {
         let color = self.get_color(index).unwrap();
         let kind = self.get_kind(index);
         let result = match kind {
@@ -155,7 +184,7 @@ impl BitBoard {
         self.get_move_from_bitatom(self.color_pieces(color))
     }
 
-    fn is_killed(&self, color: piece::Color) -> bool {
+    pub fn is_killed(&self, color: piece::Color) -> bool {
         let other_color = piece::other_color(color);
         let king_bitatom = self.bit_pieces[color as usize][piece::Kind::King as usize];
         let otherking_bitatom = self.bit_pieces[other_color as usize][piece::Kind::King as usize];
@@ -185,8 +214,109 @@ impl BitBoard {
         king_face() || (self.get_move_from_color(other_color) & king_bitatom) != 0
     }
 
-    fn is_failed(&self, color: piece::Color) -> bool {
-        self.get_move_from_color(color) == 0
+    pub fn is_failed(&mut self, color: piece::Color) -> bool {
+        self.legal_moves(color).is_empty()
+    }
+
+    pub fn game_state(&mut self) -> GameState {
+        let color = self.side_color;
+        match (self.is_killed(color), self.is_failed(color)) {
+            (true, true) => GameState::Checkmated,
+            (false, true) => GameState::Stalemated,
+            (true, false) => GameState::InCheck,
+            (false, false) => GameState::Ongoing,
+        }
+    }
+
+    // 走棋后不被将军（含将帅对面）的着法
+    pub fn is_legal_move(
+        &mut self,
+        color: piece::Color,
+        from_index: usize,
+        to_index: usize,
+    ) -> bool {
+        match self.do_move(from_index, to_index) {
+            Some(eat_kind) => {
+                let is_legal = eat_kind == piece::Kind::King || !self.is_killed(color);
+                self.undo_move(from_index, to_index, eat_kind);
+
+                is_legal
+            }
+            None => false,
+        }
+    }
+
+    // 可吃子的着法(未检验走棋后是否被将军)
+    pub fn get_capture_from_to_indexs(&self, color: piece::Color) -> Vec<(usize, usize)> {
+        let other_pieces = self.color_pieces(piece::other_color(color));
+        let mut result = vec![];
+        for from_index in bit_constant::get_indexs_from_bitatom(self.color_pieces(color)) {
+            let capture_bitatom = self.get_move_from_index(from_index) & other_pieces;
+            for to_index in bit_constant::get_indexs_from_bitatom(capture_bitatom) {
+                result.push((from_index, to_index));
+            }
+        }
+
+        result
+    }
+
+    pub fn legal_from_to_indexs(&mut self, color: piece::Color) -> Vec<(usize, usize)> {
+        let mut result = vec![];
+        for from_index in bit_constant::get_indexs_from_bitatom(self.color_pieces(color)) {
+            for to_index in
+                bit_constant::get_indexs_from_bitatom(self.get_move_from_index(from_index))
+            {
+                if self.is_legal_move(color, from_index, to_index) {
+                    result.push((from_index, to_index));
+                }
+            }
+        }
+
+        result
+    }
+
+    // 走棋方深度为depth的叶节点数，用于校验着法生成
+    pub fn perft(&mut self, depth: usize) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+
+        let from_to_indexs = self.legal_from_to_indexs(self.side_color);
+        if depth == 1 {
+            return from_to_indexs.len();
+        }
+
+        from_to_indexs
+            .into_iter()
+            .map(|(from_index, to_index)| {
+                let eat_kind = self.do_move(from_index, to_index).unwrap();
+                let count = self.perft(depth - 1);
+                self.undo_move(from_index, to_index, eat_kind);
+
+                count
+            })
+            .sum()
+    }
+
+    // 走棋方各着法之后的叶节点数
+    pub fn divide(&mut self, depth: usize) -> Vec<(CoordPair, usize)> {
+        self.legal_from_to_indexs(self.side_color)
+            .into_iter()
+            .map(|(from_index, to_index)| {
+                let eat_kind = self.do_move(from_index, to_index).unwrap();
+                let count = self.perft(depth.saturating_sub(1));
+                self.undo_move(from_index, to_index, eat_kind);
+
+                (CoordPair::from_index(from_index, to_index).unwrap(), count)
+            })
+            .collect()
+    }
+
+    pub fn legal_moves(&mut self, color: piece::Color) -> Vec<CoordPair> {
+        self.legal_from_to_indexs(color)
+            .into_iter()
+            .map(|(from_index, to_index)| CoordPair::from_index(from_index, to_index).unwrap())
+            .collect()
     }
 
     pub fn do_move(&mut self, from_index: usize, to_index: usize) -> Option<piece::Kind> {
@@ -269,6 +399,8 @@ impl BitBoard {
             self.lock ^= bit_constant::ZOBRISTLOCK[to_color_i][eat_kind_i][to_index];
         }
 
+        self.side_color = piece::other_color(self.side_color);
+
         Some(eat_kind)
     }
 
@@ -338,11 +470,11 @@ impl BitBoard {
         )
     }
 
-    fn get_key(&self, color: piece::Color) -> u64 {
+    pub fn get_key(&self, color: piece::Color) -> u64 {
         self.key ^ bit_constant::COLORZOBRISTKEY[color as usize]
     }
 
-    fn get_lock(&self, color: piece::Color) -> u64 {
+    pub fn get_lock(&self, color: piece::Color) -> u64 {
         self.lock ^ bit_constant::COLORZOBRISTLOCK[color as usize]
     }
 
@@ -351,7 +483,7 @@ impl BitBoard {
         Zorbist::from(self.get_key(color), aspect)
     }
 
-    pub fn get_key_asp_amove(&mut self, amove: &Rc<amove::Move>) -> Option<(u64, Aspect)> {
+    pub fn get_key_asp_amove(&mut self, amove: &Arc<amove::Move>) -> Option<(u64, Aspect)> {
         let (from_index, to_index) = amove.coordpair.from_to_index();
         let color = self.get_color(from_index).unwrap();
         let eval =
@@ -477,4 +609,94 @@ mod tests {
             // dbg!(bit_board);
         }
     }
+
+    #[test]
+    fn test_legal_moves() {
+        let mut bit_board = BitBoard::new();
+        assert_eq!(bit_board.side_color(), piece::Color::Red);
+        assert_eq!(bit_board.legal_moves(piece::Color::Red).len(), 44);
+        assert_eq!(bit_board.legal_moves(piece::Color::Black).len(), 44);
+
+        // 走棋后交换走棋方，悔棋后复原
+        let eat_kind = bit_board.do_move(64, 67).unwrap();
+        assert_eq!(bit_board.side_color(), piece::Color::Black);
+        bit_board.undo_move(64, 67, eat_kind);
+        assert_eq!(bit_board, BitBoard::new());
+
+        // 将帅不能对面
+        let mut bit_board = BitBoard::from(&board::fen_to_pieces("3k5/9/9/9/9/9/9/9/9/4K4"));
+        let to_indexs: Vec<usize> = bit_board
+            .legal_moves(piece::Color::Red)
+            .iter()
+            .map(|coordpair| coordpair.to_coord.index())
+            .collect();
+        assert_eq!(to_indexs, vec![76, 86]);
+        assert_eq!(bit_board.legal_moves(piece::Color::Black).len(), 1);
+    }
+
+    #[test]
+    fn test_game_state() {
+        for (fen, color, state) in [
+            (board::FEN, piece::Color::Red, GameState::Ongoing),
+            (
+                "3k5/9/9/9/9/9/9/9/3R5/5K3",
+                piece::Color::Black,
+                GameState::InCheck,
+            ),
+            (
+                "3k5/3R5/3R5/9/9/9/9/9/9/4K4",
+                piece::Color::Black,
+                GameState::Checkmated,
+            ),
+            (
+                "3k5/5R3/9/9/9/9/9/9/9/4K4",
+                piece::Color::Black,
+                GameState::Stalemated,
+            ),
+        ] {
+            let mut bit_board = BitBoard::from(&board::fen_to_pieces(fen));
+            bit_board.set_side_color(color);
+            assert_eq!(bit_board.game_state(), state, "fen: {fen}");
+        }
+    }
+
+    #[test]
+    fn test_perft() {
+        // 起始局面深度0-3的公认数值，与Fairy-Stockfish等引擎的xiangqi perft结果一致
+        let mut bit_board = BitBoard::new();
+        assert_eq!(
+            (0..=3)
+                .map(|depth| bit_board.perft(depth))
+                .collect::<Vec<_>>(),
+            vec![1, 44, 1920, 79666]
+        );
+        assert_eq!(bit_board, BitBoard::new());
+
+        let divide = bit_board.divide(2);
+        assert_eq!(divide.len(), 44);
+        assert_eq!(divide.iter().map(|&(_, count)| count).sum::<usize>(), 1920);
+
+        // common::FEN_BOARD_STRINGS中的局面：红方、黑方先走的深度1-3。
+        // 数值已用另行按规则编写、不共用本仓库代码的走法生成器逐一核对，
+        // 该生成器对起始局面同样得出44、1920、79666
+        let perft_values = [
+            ([33, 737, 21450], [25, 813, 16981]),
+            ([37, 1081, 34041], [32, 1072, 32596]),
+            ([20, 220, 4618], [11, 225, 2729]),
+        ];
+        for ((fen, _), (red_values, black_values)) in
+            common::FEN_BOARD_STRINGS[1..].iter().zip(perft_values)
+        {
+            for (color, values) in [
+                (piece::Color::Red, red_values),
+                (piece::Color::Black, black_values),
+            ] {
+                let mut bit_board = BitBoard::from(&board::fen_to_pieces(fen));
+                bit_board.set_side_color(color);
+                for (depth, value) in values.into_iter().enumerate() {
+                    assert_eq!(bit_board.perft(depth + 1), value, "fen: {fen} {color:?}");
+                }
+            }
+        }
+    }
 }
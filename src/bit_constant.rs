@@ -126,17 +126,21 @@ pub fn get_indexs_from_bitatom(bit_atom: BitAtom) -> Vec<usize> {
     index_array[0..count].to_vec()
 }
 
-pub fn get_kind_put_indexs(kind: piece::Kind, is_bottom: bool) -> Vec<usize> {
+pub fn get_kind_put(kind: piece::Kind, is_bottom: bool) -> BitAtom {
     let side = is_bottom as usize;
     match kind {
-        piece::Kind::King => get_indexs_from_bitatom(KINGPUT[side]),
-        piece::Kind::Advisor => get_indexs_from_bitatom(ADVISORPUT[side]),
-        piece::Kind::Bishop => get_indexs_from_bitatom(BISHOPPUT[side]),
-        piece::Kind::Pawn => get_indexs_from_bitatom(PAWNPUT[side]),
-        _ => (0..SEATCOUNT).collect(),
+        piece::Kind::King => KINGPUT[side],
+        piece::Kind::Advisor => ADVISORPUT[side],
+        piece::Kind::Bishop => BISHOPPUT[side],
+        piece::Kind::Pawn => PAWNPUT[side],
+        _ => KNIGHTROOKCANNONPUT,
     }
 }
 
+pub fn get_kind_put_indexs(kind: piece::Kind, is_bottom: bool) -> Vec<usize> {
+    get_indexs_from_bitatom(get_kind_put(kind, is_bottom))
+}
+
 // 随机数之xorShift128Plus
 // uint64_t s[2];
 
@@ -158,7 +162,7 @@ pub fn get_kind_put_indexs(kind: piece::Kind, is_bottom: bool) -> Vec<usize> {
 //     return s[1] + s0;
 // }
 
-const fn xorshift64(prev_value: u64) -> u64 {
+pub const fn xorshift64(prev_value: u64) -> u64 {
     let mut next = prev_value;
     next ^= next << 13;
     next ^= next >> 7;
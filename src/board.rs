@@ -4,6 +4,7 @@
 use crate::amove;
 use crate::bit_board;
 use crate::bit_constant;
+use crate::common;
 use crate::coord::Coord;
 use crate::coord::CoordPair;
 use crate::coord::{self, ChangeType};
@@ -14,7 +15,7 @@ use std::collections::HashMap;
 // use std::rc::Rc;
 // use std::rc::Weak;
 use num_enum::TryFromPrimitive;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub type Pieces = [piece::Piece; coord::SEATCOUNT];
 
@@ -26,6 +27,18 @@ pub enum MoveDir {
     Forward,
 }
 
+// 局面存在的问题
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardProblem {
+    // 将帅缺少或多余
+    KingCount(piece::Color, usize),
+    PieceCount(piece::Color, piece::Kind, usize),
+    InvalidPlace(piece::Color, piece::Kind, usize),
+    KingFace,
+    // 非走棋方正被将军
+    OtherKilled(piece::Color),
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Board {
     pieces: Pieces,
@@ -33,6 +46,9 @@ pub struct Board {
 
 pub const FEN: &str = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR";
 
+// 各种棋子的最多数量[kind]
+pub const KINDMAXCOUNTS: [usize; piece::KINDCOUNT] = [1, 2, 2, 2, 2, 2, 5];
+
 const FENSPLITCHAR: char = '/';
 
 const NUMCHARS: [[char; coord::COLCOUNT]; piece::COLORCOUNT] = [
@@ -43,6 +59,13 @@ const NUMCHARS: [[char; coord::COLCOUNT]; piece::COLORCOUNT] = [
 const POSCHARS: [char; 3] = ['前', '中', '后'];
 
 const MOVECHARS: [char; 3] = ['退', '平', '进'];
+// 繁体字的位置及走法
+const ZHVARIANTS: [(char, char); 2] = [('後', '后'), ('進', '进')];
+
+// WXF记法：棋子字母[kind]，位置及走法符号分别对应POSCHARS、MOVECHARS
+const WXFKINDCHARS: [char; piece::KINDCOUNT] = ['K', 'A', 'E', 'H', 'R', 'C', 'P'];
+const WXFPOSCHARS: [char; 3] = ['+', '=', '-'];
+const WXFMOVECHARS: [char; 3] = ['-', '=', '+'];
 
 pub fn piece_chars_to_fen(piece_chars: &str) -> String {
     fn push_num_str(result: &mut String, null_num: &mut i32) {
@@ -169,6 +192,108 @@ impl Board {
         }
     }
 
+    pub fn from_pieces(pieces: Pieces) -> Self {
+        Board { pieces }
+    }
+
+    // 以将帅所在九宫确定底部一方，将帅数量不对时默认红方在底部
+    fn get_put_bottom_color(
+        &self,
+        color_kind_indexs: &[[Vec<usize>; piece::KINDCOUNT]],
+    ) -> piece::Color {
+        for color in piece::COLORARRAY {
+            let king_indexs = &color_kind_indexs[color as usize][piece::Kind::King as usize];
+            if king_indexs.len() == 1 {
+                let is_bottom = bit_constant::get_kind_put(piece::Kind::King, true)
+                    & bit_constant::MASK[king_indexs[0]]
+                    != 0;
+                return if is_bottom {
+                    color
+                } else {
+                    piece::other_color(color)
+                };
+            }
+        }
+
+        piece::Color::Red
+    }
+
+    // 棋子数量及位置的问题
+    pub fn validate_pieces(&self) -> Vec<BoardProblem> {
+        let mut color_kind_indexs: [[Vec<usize>; piece::KINDCOUNT]; piece::COLORCOUNT] =
+            Default::default();
+        for (index, piece) in self.pieces.iter().enumerate() {
+            if let piece::Piece::Some(color, kind) = *piece {
+                if kind != piece::Kind::NoKind {
+                    color_kind_indexs[color as usize][kind as usize].push(index);
+                }
+            }
+        }
+
+        let mut result = vec![];
+        for color in piece::COLORARRAY {
+            for kind in piece::KINDARRAY {
+                let count = color_kind_indexs[color as usize][kind as usize].len();
+                if kind == piece::Kind::King && count != 1 {
+                    result.push(BoardProblem::KingCount(color, count));
+                } else if count > KINDMAXCOUNTS[kind as usize] {
+                    result.push(BoardProblem::PieceCount(color, kind, count));
+                }
+            }
+        }
+
+        let bottom_color = self.get_put_bottom_color(&color_kind_indexs);
+        for color in piece::COLORARRAY {
+            for kind in piece::KINDARRAY {
+                let put = bit_constant::get_kind_put(kind, color == bottom_color);
+                for &index in &color_kind_indexs[color as usize][kind as usize] {
+                    if put & bit_constant::MASK[index] == 0 {
+                        result.push(BoardProblem::InvalidPlace(color, kind, index));
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    // 局面的全部问题，color为走棋方；双方将帅各一个时才检查将帅状态
+    pub fn validate(&self, color: piece::Color) -> Vec<BoardProblem> {
+        let mut result = self.validate_pieces();
+        let bit_board = self.bit_board();
+        let king_indexs = |color| bit_board.get_indexs_from_color_kind(color, piece::Kind::King);
+        let (red_indexs, black_indexs) = (
+            king_indexs(piece::Color::Red),
+            king_indexs(piece::Color::Black),
+        );
+        if red_indexs.len() != 1 || black_indexs.len() != 1 {
+            return result;
+        }
+
+        let (red_index, black_index) = (red_indexs[0], black_indexs[0]);
+        let (top_index, bottom_index) = (red_index.min(black_index), red_index.max(black_index));
+        let is_king_face = top_index % coord::COLCOUNT == bottom_index % coord::COLCOUNT
+            && (top_index + coord::COLCOUNT..bottom_index)
+                .step_by(coord::COLCOUNT)
+                .all(|index| self.pieces[index] == piece::Piece::None);
+        if is_king_face {
+            result.push(BoardProblem::KingFace);
+        }
+
+        // 不计将帅对面，走棋方的棋子可直接吃对方将帅
+        let other_color = piece::other_color(color);
+        let other_king_index = king_indexs(other_color)[0];
+        if bit_board
+            .get_capture_from_to_indexs(color)
+            .iter()
+            .any(|&(_, to_index)| to_index == other_king_index)
+        {
+            result.push(BoardProblem::OtherKilled(other_color));
+        }
+
+        result
+    }
+
     pub fn get_fen(&self) -> String {
         piece_chars_to_fen(&pieces_to_piece_chars(&self.pieces))
     }
@@ -177,7 +302,14 @@ impl Board {
         bit_board::BitBoard::from(&self.pieces)
     }
 
-    pub fn do_move(&mut self, amove: &Rc<amove::Move>) -> piece::Piece {
+    pub fn game_state(&self, color: piece::Color) -> bit_board::GameState {
+        let mut bit_board = self.bit_board();
+        bit_board.set_side_color(color);
+
+        bit_board.game_state()
+    }
+
+    pub fn do_move(&mut self, amove: &Arc<amove::Move>) -> piece::Piece {
         let (from_index, to_index) = amove.coordpair.from_to_index();
         let to_piece = self.pieces[to_index];
         self.pieces[to_index] = self.pieces[from_index];
@@ -186,14 +318,14 @@ impl Board {
         to_piece
     }
 
-    pub fn undo_move(&mut self, amove: &Rc<amove::Move>, to_piece: piece::Piece) {
+    pub fn undo_move(&mut self, amove: &Arc<amove::Move>, to_piece: piece::Piece) {
         let (from_index, to_index) = amove.coordpair.from_to_index();
 
         self.pieces[from_index] = self.pieces[to_index];
         self.pieces[to_index] = to_piece;
     }
 
-    pub fn to_move(&self, amove: &Rc<amove::Move>, contains_self: bool) -> Self {
+    pub fn to_move(&self, amove: &Arc<amove::Move>, contains_self: bool) -> Self {
         let mut board = self.clone();
         for amove in amove.before_moves(contains_self) {
             board.do_move(&amove);
@@ -265,32 +397,54 @@ impl Board {
                 Coord::get_side_col(to_col, color_is_bottom)
             };
             result.push(Self::get_col_ch(color, num_or_col));
+            assert_eq!(
+                self.get_coordpair_from_zhstr(&result, color),
+                Ok(*coordpair)
+            );
         }
-        assert_eq!(self.get_coordpair_from_zhstr(&result), coordpair.clone());
 
         result
     }
 
-    pub fn get_coordpair_from_zhstr(&self, zhstr: &str) -> CoordPair {
-        let zh_chs: Vec<char> = zhstr.chars().collect();
-        assert_eq!(zh_chs.len(), 4);
+    // 繁体字、异体字及半角数字转换为本库的记法，半角数字按走棋方color转换
+    pub fn normalize_zhstr(zhstr: &str, color: piece::Color) -> String {
+        zhstr
+            .trim()
+            .chars()
+            .map(|ch| match ch {
+                '1'..='9' => NUMCHARS[color as usize][ch as usize - '1' as usize],
+                _ => ZHVARIANTS
+                    .iter()
+                    .find(|&&(variant, _)| variant == ch)
+                    .map_or(piece::normalize_name(ch), |&(_, zh_ch)| zh_ch),
+            })
+            .collect()
+    }
+
+    // color为走棋方，仅用于转换半角数字，全角及中文数字自带走棋方
+    pub fn get_coordpair_from_zhstr(
+        &self,
+        zhstr: &str,
+        color: piece::Color,
+    ) -> common::Result<CoordPair> {
+        let invalid = || common::GenerateError::InvalidZhMove(zhstr.to_string());
+        let zh_chs: Vec<char> = Self::normalize_zhstr(zhstr, color).chars().collect();
+        if zh_chs.len() != 4 {
+            return Err(invalid());
+        }
 
         let color = Self::get_color(zh_chs[3]);
         let color_is_bottom = color == get_bottom_color(&self.pieces);
         let mut index = 0;
-        let move_dir = Self::get_move_dir(zh_chs[2]);
+        let move_dir = Self::get_move_dir(zh_chs[2]).ok_or_else(invalid)?;
         let abs_row_sub = (move_dir == MoveDir::Forward) == color_is_bottom;
 
         let mut live_coords: Vec<Coord>;
         let mut kind = piece::kind_from_name(zh_chs[0]);
         if kind != piece::Kind::NoKind {
-            let col = Self::get_col(color, zh_chs[1]);
+            let col = Self::get_col(color, zh_chs[1]).ok_or_else(invalid)?;
             let from_col = Coord::get_side_col(col, color_is_bottom);
             live_coords = self.get_coords_from_color_kind_col(color, kind, from_col);
-            assert!(
-                live_coords.len() > 0,
-                "board:{self:?}\ncolor:{color:?} kind:{kind:?} from_col:{from_col}"
-            );
 
             // 士、象同列时不分前后，以进、退区分棋子位置
             if live_coords.len() == 2 && move_dir == MoveDir::Forward {
@@ -303,30 +457,37 @@ impl Board {
             } else {
                 self.get_coords_from_color_kind(color, kind)
             };
-            assert!(live_coords.len() > 1);
+            if live_coords.len() < 2 {
+                return Err(invalid());
+            }
 
             let pre_chars = Self::get_pre_chars(live_coords.len());
-            index = pre_chars.iter().position(|&ch| ch == zh_chs[0]).unwrap();
+            index = pre_chars
+                .iter()
+                .position(|&ch| ch == zh_chs[0])
+                .ok_or_else(invalid)?;
+        }
+        if live_coords.len() <= index {
+            return Err(invalid());
         }
-        assert!(live_coords.len() > index);
 
         Self::sort_coords(&mut live_coords, color_is_bottom);
         let from_coord = live_coords[index];
-        let mut to_row = from_coord.row;
-        let col = Self::get_col(color, zh_chs[3]);
+        let mut to_row = from_coord.row as isize;
+        let col = Self::get_col(color, zh_chs[3]).ok_or_else(invalid)?;
         let mut to_col = Coord::get_side_col(col, color_is_bottom);
         if piece::is_line_move(kind) {
             if move_dir != MoveDir::Parallel {
                 to_col = from_coord.col;
                 if abs_row_sub {
-                    to_row -= col + 1;
+                    to_row -= col as isize + 1;
                 } else {
-                    to_row += col + 1;
+                    to_row += col as isize + 1;
                 }
             }
         } else {
             // 斜线走子：仕、相、马
-            let col_away = (to_col as isize - from_coord.col as isize).abs() as usize;
+            let col_away = (to_col as isize - from_coord.col as isize).abs();
             //  相距1或2列
             let row_inc = if kind == piece::Kind::Advisor || kind == piece::Kind::Bishop {
                 col_away
@@ -345,8 +506,89 @@ impl Board {
             }
         }
 
-        let to_coord = Coord::from(to_row, to_col).unwrap();
-        CoordPair::from(from_coord, to_coord)
+        let to_row = usize::try_from(to_row).map_err(|_| invalid())?;
+        let to_coord = Coord::from(to_row, to_col).map_err(|_| invalid())?;
+        Ok(CoordPair::from(from_coord, to_coord))
+    }
+
+    // 由中文记法逐字转换，如"炮二平五"为"C2=5"，"前马进七"为"+H+7"
+    pub fn get_wxfstr_from_coordpair(&self, coordpair: &CoordPair) -> String {
+        let zhstr = self.get_zhstr_from_coordpair(coordpair);
+        let mut result = String::new();
+        for zh_ch in zhstr.chars() {
+            let kind = piece::kind_from_name(zh_ch);
+            result.push(if kind != piece::Kind::NoKind {
+                WXFKINDCHARS[kind as usize]
+            } else if let Some(pos) = POSCHARS.iter().position(|&ch| ch == zh_ch) {
+                WXFPOSCHARS[pos]
+            } else if let Some(pos) = MOVECHARS.iter().position(|&ch| ch == zh_ch) {
+                WXFMOVECHARS[pos]
+            } else {
+                let col = Self::get_col(Self::get_color(zh_ch), zh_ch).unwrap_or(0);
+                char::from_digit(col as u32 + 1, 10).unwrap()
+            });
+        }
+
+        result
+    }
+
+    // WXF记法不区分棋子颜色，须指定走棋方；同列棋子的位置符号可在棋子字母之前或之后
+    pub fn get_coordpair_from_wxfstr(
+        &self,
+        wxfstr: &str,
+        color: piece::Color,
+    ) -> common::Result<CoordPair> {
+        let invalid = || common::GenerateError::InvalidWxfMove(wxfstr.to_string());
+        let mut wxf_chs: Vec<char> = wxfstr.chars().map(|ch| ch.to_ascii_uppercase()).collect();
+        if wxf_chs.len() != 4 {
+            return Err(invalid());
+        }
+
+        let get_kind = |wxf_ch| match wxf_ch {
+            'B' => Some(piece::Kind::Bishop),
+            'N' => Some(piece::Kind::Knight),
+            _ => WXFKINDCHARS
+                .iter()
+                .position(|&ch| ch == wxf_ch)
+                .and_then(|index| piece::Kind::try_from_primitive(index).ok()),
+        };
+        let get_num_ch = |wxf_ch: char, color: piece::Color| {
+            wxf_ch
+                .to_digit(10)
+                .filter(|&num| num > 0)
+                .map(|num| NUMCHARS[color as usize][num as usize - 1])
+        };
+        if get_kind(wxf_chs[0]).is_some() && WXFPOSCHARS.contains(&wxf_chs[1]) {
+            wxf_chs.swap(0, 1);
+        }
+
+        let mut zhstr = String::new();
+        if let Some(kind) = get_kind(wxf_chs[0]) {
+            zhstr.push(piece::NAMECHARS[color as usize][kind as usize]);
+            zhstr.push(get_num_ch(wxf_chs[1], color).ok_or_else(invalid)?);
+        } else {
+            // 多兵时以一至五标记前后次序
+            let pos_ch = match WXFPOSCHARS.iter().position(|&ch| ch == wxf_chs[0]) {
+                Some(pos) => POSCHARS[pos],
+                None => get_num_ch(wxf_chs[0], piece::Color::Red).ok_or_else(invalid)?,
+            };
+            let kind = get_kind(wxf_chs[1]).ok_or_else(invalid)?;
+            zhstr.push(pos_ch);
+            zhstr.push(piece::NAMECHARS[color as usize][kind as usize]);
+        }
+        let move_ch = match wxf_chs[2] {
+            '.' => MOVECHARS[1],
+            move_ch => WXFMOVECHARS
+                .iter()
+                .position(|&ch| ch == move_ch)
+                .map(|pos| MOVECHARS[pos])
+                .ok_or_else(invalid)?,
+        };
+        zhstr.push(move_ch);
+        zhstr.push(get_num_ch(wxf_chs[3], color).ok_or_else(invalid)?);
+
+        self.get_coordpair_from_zhstr(&zhstr, color)
+            .map_err(|_| invalid())
     }
 
     fn get_coords_from_color_kind(&self, color: piece::Color, kind: piece::Kind) -> Vec<Coord> {
@@ -412,11 +654,10 @@ impl Board {
         NUMCHARS[color as usize][col]
     }
 
-    fn get_col(color: piece::Color, col_char: char) -> usize {
+    fn get_col(color: piece::Color, col_char: char) -> Option<usize> {
         NUMCHARS[color as usize]
             .iter()
             .position(|&ch| ch == col_char)
-            .unwrap()
     }
 
     fn get_color(num_ch: char) -> piece::Color {
@@ -447,9 +688,11 @@ impl Board {
         }]
     }
 
-    fn get_move_dir(move_ch: char) -> MoveDir {
-        MoveDir::try_from_primitive(MOVECHARS.iter().position(|&ch| ch == move_ch).unwrap())
-            .unwrap()
+    fn get_move_dir(move_ch: char) -> Option<MoveDir> {
+        MOVECHARS
+            .iter()
+            .position(|&ch| ch == move_ch)
+            .and_then(|index| MoveDir::try_from_primitive(index).ok())
     }
 
     pub fn get_pgnzh_pattern() -> String {
@@ -460,15 +703,26 @@ impl Board {
         )
     }
 
+    pub fn get_pgnwxf_pattern() -> String {
+        let kind_chars = "KAEBHNRCPkaebhnrcp";
+        format!(r"(?:[{kind_chars}][1-9+=-]|[+=1-5-][{kind_chars}])[+=.-][1-9]")
+    }
+
     pub fn get_pgnzh_pattern_color(color: piece::Color) -> String {
         let mut name_chars = String::new();
         for ch in piece::NAMECHARS[color as usize] {
             name_chars.push(ch);
         }
+        for (ch, _) in piece::NAMEVARIANTS {
+            name_chars.push(ch);
+        }
         let mut num_chars = String::new();
         for ch in NUMCHARS[color as usize] {
             num_chars.push(ch);
         }
+        if color == piece::Color::Black {
+            num_chars.push_str("1-9");
+        }
         let mut pos_chars = String::new();
         for ch in POSCHARS {
             pos_chars.push(ch);
@@ -477,6 +731,13 @@ impl Board {
         for ch in MOVECHARS {
             move_chars.push(ch);
         }
+        for (ch, zh_ch) in ZHVARIANTS {
+            if POSCHARS.contains(&zh_ch) {
+                pos_chars.push(ch);
+            } else {
+                move_chars.push(ch);
+            }
+        }
 
         format!(
             "[{}{}{}]{{2}}[{}][{}]",
@@ -504,6 +765,158 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_validate() {
+        for (fen, _, _) in common::FEN_PIECES_CHARS {
+            assert!(
+                Board::from(fen).validate(piece::Color::Red).is_empty(),
+                "fen: {fen}"
+            );
+        }
+
+        for (fen, problems) in [
+            (
+                "9/9/9/9/9/9/9/9/9/4K4",
+                vec![BoardProblem::KingCount(piece::Color::Black, 0)],
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/RRRKK4",
+                vec![
+                    BoardProblem::KingCount(piece::Color::Red, 2),
+                    BoardProblem::PieceCount(piece::Color::Red, piece::Kind::Rook, 3),
+                ],
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/B3K1B2",
+                vec![
+                    BoardProblem::InvalidPlace(piece::Color::Red, piece::Kind::Bishop, 81),
+                    BoardProblem::KingFace,
+                ],
+            ),
+            ("3k5/9/P8/9/9/9/9/9/9/4K4", vec![]),
+            (
+                "4k4/9/9/9/9/9/9/P8/9/4K4",
+                vec![
+                    BoardProblem::InvalidPlace(piece::Color::Red, piece::Kind::Pawn, 63),
+                    BoardProblem::KingFace,
+                ],
+            ),
+            ("4k4/9/9/9/9/9/9/9/9/4K4", vec![BoardProblem::KingFace]),
+            (
+                "3k5/9/9/9/9/9/9/9/9/4K1r2",
+                vec![BoardProblem::OtherKilled(piece::Color::Red)],
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/4K1r2",
+                vec![
+                    BoardProblem::KingFace,
+                    BoardProblem::OtherKilled(piece::Color::Red),
+                ],
+            ),
+        ] {
+            assert_eq!(
+                Board::from(fen).validate(piece::Color::Black),
+                problems,
+                "fen: {fen}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalize_zhstr() {
+        let board = Board::new();
+        let (red, black) = (piece::Color::Red, piece::Color::Black);
+        for (variant, zhstr, color) in [
+            ("車一進一", "车一进一", red),
+            ("俥九進一", "车九进一", red),
+            ("傌二進三", "马二进三", red),
+            ("炮2平5", "炮二平五", red),
+            ("馬8進7", "马８进７", black),
+            ("砲８平５", "炮８平５", black),
+            ("帥五進一", "帅五进一", red),
+        ] {
+            assert_eq!(
+                board.get_coordpair_from_zhstr(variant, color),
+                board.get_coordpair_from_zhstr(zhstr, color),
+                "variant: {variant}"
+            );
+            assert!(board.get_coordpair_from_zhstr(variant, color).is_ok());
+        }
+        assert_ne!(
+            board.get_coordpair_from_zhstr("炮2平5", red),
+            board.get_coordpair_from_zhstr("炮2平5", black)
+        );
+
+        let board = Board::from("3k5/9/9/4P4/4P4/4P4/9/9/9/4K4");
+        assert_eq!(
+            board.get_coordpair_from_zhstr("後兵平四", red),
+            board.get_coordpair_from_zhstr("后兵平四", red)
+        );
+        assert_eq!(
+            board.get_coordpair_from_zhstr("車九進十", red),
+            Err(common::GenerateError::InvalidZhMove("車九進十".to_string()))
+        );
+
+        let pattern = regex::Regex::new(&Board::get_pgnzh_pattern()).unwrap();
+        for zhstr in ["車一進一", "馬8進7", "後兵平四", "前卒平５"] {
+            assert!(pattern.is_match(zhstr), "zhstr: {zhstr}");
+        }
+    }
+
+    #[test]
+    fn test_wxf() {
+        let board = Board::new();
+        for (wxfstr, zhstr, color) in [
+            ("C2=5", "炮二平五", piece::Color::Red),
+            ("c2.5", "炮二平五", piece::Color::Red),
+            ("H8+7", "马８进７", piece::Color::Black),
+            ("N8+7", "马８进７", piece::Color::Black),
+        ] {
+            let coordpair = board.get_coordpair_from_zhstr(zhstr, color).unwrap();
+            assert_eq!(
+                board.get_coordpair_from_wxfstr(wxfstr, color),
+                Ok(coordpair)
+            );
+        }
+
+        let board = Board::from("3k5/9/9/4P4/4P4/4P4/9/9/9/4K4");
+        for (wxfstr, zhstr) in [
+            ("+P+1", "前兵进一"),
+            ("=P=6", "中兵平六"),
+            ("P-=4", "后兵平四"),
+        ] {
+            let coordpair = board
+                .get_coordpair_from_zhstr(zhstr, piece::Color::Red)
+                .unwrap();
+            assert_eq!(
+                board.get_wxfstr_from_coordpair(&coordpair),
+                wxfstr.replace("P-", "-P")
+            );
+            assert_eq!(
+                board.get_coordpair_from_wxfstr(wxfstr, piece::Color::Red),
+                Ok(coordpair)
+            );
+        }
+        assert!(board
+            .get_coordpair_from_wxfstr("C2=5", piece::Color::Red)
+            .is_err());
+
+        let fens = common::FEN_PIECES_CHARS.map(|(fen, _, _)| fen);
+        for fen in fens.iter().chain(["3k5/9/9/4P4/4P4/4P4/9/9/9/4K4"].iter()) {
+            let board = Board::from(fen);
+            for color in piece::COLORARRAY {
+                for coordpair in board.bit_board().legal_moves(color) {
+                    let wxfstr = board.get_wxfstr_from_coordpair(&coordpair);
+                    assert_eq!(
+                        board.get_coordpair_from_wxfstr(&wxfstr, color),
+                        Ok(coordpair),
+                        "fen: {fen} wxf: {wxfstr}"
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_board() {
         for (fen, piece_chars, to_string) in common::FEN_PIECES_CHARS {
@@ -0,0 +1,437 @@
+#![allow(dead_code)]
+
+use crate::bit_board::BitBoard;
+use crate::bit_constant;
+use crate::coord::{self, CoordPair};
+use crate::evaluation::{Aspect, Evaluation, Zorbist};
+use crate::piece::{self, COLORCOUNT, KINDCOUNT};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+// 文件格式(小端)：
+// 头部16字节：MAGIC(8) + 条目数u64
+// 条目24字节：key u64 + lock u64 + from u8 + to u8 + 保留u16 + weight u32
+// 条目按(key, lock, from, to)升序排列，key为碰撞链上分配的键
+const MAGIC: &[u8; 8] = b"XQBOOK\x00\x01";
+const HEADERSIZE: usize = 16;
+const ENTRYSIZE: usize = 24;
+
+// 外部(类Polyglot)开局库条目16字节(大端)：key u64 + move u16 + weight u16 + learn u32
+// 格位以红方底线为第0行：square = (9 - row) * 9 + col，move = from << 7 | to
+const FOREIGNENTRYSIZE: usize = 16;
+
+type ForeignKeyArray = [[[u64; coord::SEATCOUNT]; KINDCOUNT]; COLORCOUNT];
+
+// 外部程序的Zobrist表，键为各棋子所在格位键值的异或，黑方走棋时再异或side_key
+pub struct ForeignZobrist {
+    keys: Box<ForeignKeyArray>,
+    side_key: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BookEntry {
+    pub key: u64,
+    pub lock: u64,
+    pub from: u8,
+    pub to: u8,
+    pub weight: u32,
+}
+
+pub struct Book {
+    data: Vec<u8>,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        BookEntry {
+            key: read_u64(bytes),
+            lock: read_u64(&bytes[8..]),
+            from: bytes[16],
+            to: bytes[17],
+            weight: u32::from_le_bytes(bytes[20..24].try_into().unwrap()),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRYSIZE] {
+        let mut bytes = [0; ENTRYSIZE];
+        bytes[..8].copy_from_slice(&self.key.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.lock.to_le_bytes());
+        bytes[16] = self.from;
+        bytes[17] = self.to;
+        bytes[20..].copy_from_slice(&self.weight.to_le_bytes());
+
+        bytes
+    }
+}
+
+impl Book {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Self> {
+        if data.len() < HEADERSIZE || &data[..8] != MAGIC {
+            return Err(invalid_data("not a book file"));
+        }
+
+        let book = Book { data };
+        // 条目数取自文件头，计算大小时须防溢出
+        let size = book
+            .len()
+            .checked_mul(ENTRYSIZE)
+            .and_then(|size| size.checked_add(HEADERSIZE));
+        if size != Some(book.data.len()) {
+            return Err(invalid_data("book file size mismatch"));
+        }
+
+        Ok(book)
+    }
+
+    pub fn write(path: &Path, mut entries: Vec<BookEntry>) -> io::Result<()> {
+        entries.sort();
+
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for entry in entries {
+            writer.write_all(&entry.to_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn len(&self) -> usize {
+        read_u64(&self.data[8..]) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get_entry(&self, index: usize) -> BookEntry {
+        let start = HEADERSIZE + index * ENTRYSIZE;
+        BookEntry::from_bytes(&self.data[start..start + ENTRYSIZE])
+    }
+
+    // 键为key的条目范围
+    fn key_range(&self, key: u64) -> std::ops::Range<usize> {
+        let lower_bound = |key: u64| {
+            let (mut low, mut high) = (0, self.len());
+            while low < high {
+                let mid = (low + high) / 2;
+                if self.get_entry(mid).key < key {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+
+            low
+        };
+
+        let start = lower_bound(key);
+        let mut end = start;
+        while end < self.len() && self.get_entry(end).key == key {
+            end += 1;
+        }
+
+        start..end
+    }
+
+    // 沿碰撞链查找lock相符的条目
+    pub fn get_entries(&self, mut key: u64, lock: u64) -> Vec<BookEntry> {
+        for index in 0..=bit_constant::COLLIDEZOBRISTKEY.len() {
+            let range = self.key_range(key);
+            if range.is_empty() {
+                break;
+            }
+
+            let entries: Vec<BookEntry> = range
+                .map(|index| self.get_entry(index))
+                .filter(|entry| entry.lock == lock)
+                .collect();
+            if !entries.is_empty() {
+                return entries;
+            }
+
+            if index < bit_constant::COLLIDEZOBRISTKEY.len() {
+                key ^= bit_constant::COLLIDEZOBRISTKEY[index];
+            }
+        }
+
+        vec![]
+    }
+
+    // 局面走棋方的着法及权重，按权重降序
+    pub fn probe(&self, bit_board: &BitBoard) -> Vec<(CoordPair, usize)> {
+        let color = bit_board.side_color();
+        let mut result: Vec<(CoordPair, usize)> = self
+            .get_entries(bit_board.get_key(color), bit_board.get_lock(color))
+            .iter()
+            .filter_map(|entry| {
+                CoordPair::from_index(entry.from as usize, entry.to as usize)
+                    .ok()
+                    .map(|coordpair| (coordpair, entry.weight as usize))
+            })
+            .collect();
+        result.sort_by_key(|&(_, weight)| std::cmp::Reverse(weight));
+
+        result
+    }
+
+    pub fn probe_best(&self, bit_board: &BitBoard) -> Option<CoordPair> {
+        self.probe(bit_board)
+            .first()
+            .map(|&(coordpair, _)| coordpair)
+    }
+}
+
+fn to_square(index: usize) -> usize {
+    let (row, col) = (index / coord::COLCOUNT, index % coord::COLCOUNT);
+    (coord::ROWCOUNT - 1 - row) * coord::COLCOUNT + col
+}
+
+fn from_square(square: usize) -> usize {
+    let (rank, col) = (square / coord::COLCOUNT, square % coord::COLCOUNT);
+    (coord::ROWCOUNT - 1 - rank) * coord::COLCOUNT + col
+}
+
+impl ForeignZobrist {
+    pub fn from(keys: ForeignKeyArray, side_key: u64) -> Self {
+        ForeignZobrist {
+            keys: Box::new(keys),
+            side_key,
+        }
+    }
+
+    // 以xorshift序列生成键值表，供未提供键值表的工具使用
+    pub fn from_seed(seed: u64) -> Self {
+        let mut value = seed | 1;
+        let mut next = || {
+            value = bit_constant::xorshift64(value);
+            value
+        };
+
+        let mut keys = [[[0; coord::SEATCOUNT]; KINDCOUNT]; COLORCOUNT];
+        for color_keys in keys.iter_mut() {
+            for kind_keys in color_keys.iter_mut() {
+                for key in kind_keys.iter_mut() {
+                    *key = next();
+                }
+            }
+        }
+
+        Self::from(keys, next())
+    }
+
+    // 按外部键值表计算局面(红方在底部)的键
+    pub fn get_key(&self, bit_board: &BitBoard) -> u64 {
+        let mut key = 0;
+        for color in [piece::Color::Red, piece::Color::Black] {
+            for kind in piece::KINDARRAY {
+                for index in bit_board.get_indexs_from_color_kind(color, kind) {
+                    key ^= self.keys[color as usize][kind as usize][to_square(index)];
+                }
+            }
+        }
+
+        if bit_board.side_color() == piece::Color::Black {
+            key ^= self.side_key;
+        }
+
+        key
+    }
+
+    // 从开局局面出发，沿开局库着法遍历各局面，按外部键值表导出
+    pub fn export_book(&self, zorbist: &Zorbist, path: &Path) -> io::Result<usize> {
+        let mut entries = vec![];
+        let mut visited = HashSet::new();
+        let mut bit_boards = vec![BitBoard::new()];
+        while let Some(bit_board) = bit_boards.pop() {
+            let color = bit_board.side_color();
+            if !visited.insert((bit_board.get_key(color), bit_board.get_lock(color))) {
+                continue;
+            }
+
+            let key = self.get_key(&bit_board);
+            for (coordpair, count) in zorbist.probe(&bit_board) {
+                let (from_index, to_index) = coordpair.from_to_index();
+                let mut next_bit_board = bit_board;
+                if next_bit_board.do_move(from_index, to_index).is_none() {
+                    continue;
+                }
+
+                let amove = (to_square(from_index) << 7 | to_square(to_index)) as u16;
+                entries.push((key, amove, count.min(u16::MAX as usize) as u16));
+                bit_boards.push(next_bit_board);
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.2.cmp(&a.2)));
+
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        for &(key, amove, weight) in &entries {
+            writer.write_all(&key.to_be_bytes())?;
+            writer.write_all(&amove.to_be_bytes())?;
+            writer.write_all(&weight.to_be_bytes())?;
+            writer.write_all(&0u32.to_be_bytes())?;
+        }
+        writer.flush()?;
+
+        Ok(entries.len())
+    }
+
+    // 读取外部开局库，从开局局面出发重放着法，换算为本库的键值并计入zorbist
+    pub fn import_book(&self, path: &Path) -> io::Result<Zorbist> {
+        let data = fs::read(path)?;
+        if data.len() % FOREIGNENTRYSIZE != 0 {
+            return Err(invalid_data("foreign book file size mismatch"));
+        }
+
+        let mut key_moves: HashMap<u64, Vec<(usize, usize)>> = HashMap::new();
+        for bytes in data.chunks(FOREIGNENTRYSIZE) {
+            let amove = u16::from_be_bytes([bytes[8], bytes[9]]) as usize;
+            let weight = u16::from_be_bytes([bytes[10], bytes[11]]) as usize;
+            key_moves
+                .entry(read_u64_be(bytes))
+                .or_default()
+                .push((amove, weight));
+        }
+
+        let mut zorbist = Zorbist::new();
+        let mut visited = HashSet::new();
+        let mut bit_boards = vec![BitBoard::new()];
+        while let Some(mut bit_board) = bit_boards.pop() {
+            let color = bit_board.side_color();
+            let (key, lock) = (bit_board.get_key(color), bit_board.get_lock(color));
+            if !visited.insert((key, lock)) {
+                continue;
+            }
+
+            let Some(moves) = key_moves.get(&self.get_key(&bit_board)) else {
+                continue;
+            };
+            let legal_from_to_indexs = bit_board.legal_from_to_indexs(color);
+            for &(amove, weight) in moves {
+                let (from, to) = (amove >> 7, amove & 0x7f);
+                if from >= coord::SEATCOUNT || to >= coord::SEATCOUNT {
+                    continue;
+                }
+
+                let (from_index, to_index) = (from_square(from), from_square(to));
+                if !legal_from_to_indexs.contains(&(from_index, to_index)) {
+                    continue;
+                }
+
+                zorbist.insert(
+                    key,
+                    Aspect::from(lock, from_index, to_index, Evaluation::from(weight)),
+                );
+                let mut next_bit_board = bit_board;
+                next_bit_board.do_move(from_index, to_index);
+                bit_boards.push(next_bit_board);
+            }
+        }
+
+        Ok(zorbist)
+    }
+}
+
+fn read_u64_be(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::{Aspect, Evaluation, Zorbist};
+
+    #[test]
+    fn test_book() {
+        let mut bit_board = BitBoard::new();
+        let color = bit_board.side_color();
+        let key = bit_board.get_key(color);
+        let lock = bit_board.get_lock(color);
+        let mut zorbist = Zorbist::new();
+        zorbist.insert(key, Aspect::from(lock, 70, 67, Evaluation::from(3)));
+        zorbist.insert(key, Aspect::from(lock, 88, 69, Evaluation::from(1)));
+        // 碰撞局面存于链上下一位置
+        zorbist.insert(key, Aspect::from(!lock, 64, 67, Evaluation::from(5)));
+        bit_board.do_move(70, 67);
+        let other_color = bit_board.side_color();
+        zorbist.insert(
+            bit_board.get_key(other_color),
+            Aspect::from(bit_board.get_lock(other_color), 7, 24, Evaluation::from(2)),
+        );
+        bit_board.undo_move(70, 67, crate::piece::Kind::NoKind);
+
+        let path = Path::new("tests/output/book.bin");
+        zorbist.write_book(path).unwrap();
+        let book = Book::open(path).unwrap();
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.probe(&bit_board), zorbist.probe(&bit_board));
+        assert_eq!(book.get_entries(key, !lock).len(), 1);
+        assert!(book.get_entries(key ^ 1, lock).is_empty());
+
+        bit_board.do_move(70, 67);
+        assert_eq!(book.probe(&bit_board), zorbist.probe(&bit_board));
+        assert_eq!(book.probe_best(&bit_board), zorbist.probe_best(&bit_board));
+
+        assert!(Book::from_bytes(vec![0; HEADERSIZE]).is_err());
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&u64::MAX.to_le_bytes());
+        match Book::from_bytes(data) {
+            Err(err) => assert_eq!(err.to_string(), "book file size mismatch"),
+            Ok(_) => panic!("book with u64::MAX entries should be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_foreign_book() {
+        let mut bit_board = BitBoard::new();
+        let mut zorbist = Zorbist::new();
+        // 炮二平五 马8进7 马二进三
+        for (from_index, to_index, count) in [(70, 67, 3), (7, 24, 2), (88, 69, 1)] {
+            let color = bit_board.side_color();
+            let (key, lock) = (bit_board.get_key(color), bit_board.get_lock(color));
+            zorbist.insert(
+                key,
+                Aspect::from(lock, from_index, to_index, Evaluation::from(count)),
+            );
+            bit_board.do_move(from_index, to_index);
+        }
+
+        let foreign = ForeignZobrist::from_seed(1234);
+        let path = Path::new("tests/output/foreign_book.bin");
+        assert_eq!(foreign.export_book(&zorbist, path).unwrap(), 3);
+        assert_eq!(
+            fs::metadata(path).unwrap().len(),
+            3 * FOREIGNENTRYSIZE as u64
+        );
+
+        let import_zorbist = foreign.import_book(path).unwrap();
+        let mut bit_board = BitBoard::new();
+        for (from_index, to_index) in [(70, 67), (7, 24), (88, 69)] {
+            assert_eq!(import_zorbist.probe(&bit_board), zorbist.probe(&bit_board));
+            bit_board.do_move(from_index, to_index);
+        }
+        assert!(import_zorbist.probe(&bit_board).is_empty());
+
+        // 键值表不同则无法匹配
+        let other_foreign = ForeignZobrist::from_seed(4321);
+        assert!(other_foreign
+            .import_book(path)
+            .unwrap()
+            .probe(&BitBoard::new())
+            .is_empty());
+    }
+}
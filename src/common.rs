@@ -14,7 +14,7 @@ use std::path::{Path, PathBuf};
 
 pub type Result<T> = std::result::Result<T, GenerateError>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum GenerateError {
     RowOut,
     ColOut,
@@ -22,17 +22,78 @@ pub enum GenerateError {
     StringParse,
     RecordTypeError,
     ReadFileError,
+    InvalidBoard,
+    // 文件内容不足：读取的起始位置及所需字节数
+    Truncated {
+        offset: usize,
+        size: usize,
+    },
+    InvalidSignature,
+    InvalidChecksum,
+    InvalidVersion(u8),
+    InvalidRemarkSize(usize),
+    InvalidUtf8(usize),
+    InvalidZhMove(String),
+    InvalidWxfMove(String),
+    IllegalMove {
+        from_index: usize,
+        to_index: usize,
+    },
+    // 着法出错的位置(二进制文件为字节偏移，文本为字符串位置)、序号(自1起算)及原因
+    Move {
+        offset: usize,
+        number: usize,
+        cause: Box<GenerateError>,
+    },
+}
+
+impl GenerateError {
+    pub fn at_move(self, offset: usize, number: usize) -> Self {
+        GenerateError::Move {
+            offset,
+            number,
+            cause: Box::new(self),
+        }
+    }
 }
 
 impl fmt::Display for GenerateError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "invalid value(kind: {:?}) to coord.", self)
+        match self {
+            GenerateError::Truncated { offset, size } => {
+                write!(f, "file truncated: need {size} bytes at offset {offset}.")
+            }
+            GenerateError::InvalidSignature => write!(f, "invalid file signature."),
+            GenerateError::InvalidChecksum => write!(f, "invalid key checksum."),
+            GenerateError::InvalidVersion(version) => write!(f, "unsupported version {version}."),
+            GenerateError::InvalidRemarkSize(offset) => {
+                write!(f, "invalid remark size at offset {offset}.")
+            }
+            GenerateError::InvalidUtf8(offset) => {
+                write!(f, "invalid utf-8 string at offset {offset}.")
+            }
+            GenerateError::InvalidZhMove(zhstr) => write!(f, "invalid chinese move: {zhstr}."),
+            GenerateError::InvalidWxfMove(wxfstr) => write!(f, "invalid wxf move: {wxfstr}."),
+            GenerateError::IllegalMove {
+                from_index,
+                to_index,
+            } => write!(f, "illegal move: ({from_index}, {to_index})."),
+            GenerateError::Move {
+                offset,
+                number,
+                cause,
+            } => write!(f, "move {number} at offset {offset}: {cause}"),
+            _ => write!(f, "invalid value(kind: {:?}) to coord.", self),
+        }
     }
 }
 
 impl error::Error for GenerateError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        None
+        match self {
+            GenerateError::Move { cause, .. } => Some(cause.as_ref()),
+            _ => None,
+        }
     }
 }
 
@@ -53,11 +114,14 @@ pub fn visit_dirs(dir: &Path, cb: &dyn Fn(&DirEntry)) -> io::Result<()> {
     Ok(())
 }
 
-fn read_bytes(input: &mut &[u8], size: usize) -> Vec<u8> {
-    let (bytes, rest) = input.split_at(size);
-    *input = rest;
+// 自pos处读取size个字节，并移动pos
+fn read_bytes<'a>(input: &'a [u8], pos: &mut usize, size: usize) -> Result<&'a [u8]> {
+    let bytes = input
+        .get(*pos..*pos + size)
+        .ok_or(GenerateError::Truncated { offset: *pos, size })?;
+    *pos += size;
 
-    bytes.to_vec()
+    Ok(bytes)
 }
 
 pub fn write_coordpair(output: &mut Vec<u8>, coordpair: &CoordPair) {
@@ -74,27 +138,27 @@ pub fn write_string(output: &mut Vec<u8>, string: &str) {
     output.append(&mut string.as_bytes().to_vec());
 }
 
-pub fn read_coordpair(input: &mut &[u8]) -> CoordPair {
-    let bytes = read_bytes(input, 4);
+pub fn read_coordpair(input: &[u8], pos: &mut usize) -> Result<CoordPair> {
+    let bytes = read_bytes(input, pos, 4)?;
     CoordPair::from_row_col(
         bytes[0] as usize,
         bytes[1] as usize,
         bytes[2] as usize,
         bytes[3] as usize,
     )
-    .unwrap()
 }
 
-pub fn read_be_u32(input: &mut &[u8]) -> u32 {
-    let bytes = read_bytes(input, std::mem::size_of::<u32>());
-    u32::from_be_bytes(bytes.try_into().unwrap())
+pub fn read_be_u32(input: &[u8], pos: &mut usize) -> Result<u32> {
+    let bytes = read_bytes(input, pos, std::mem::size_of::<u32>())?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
 }
 
-pub fn read_string(input: &mut &[u8]) -> String {
-    let size = read_be_u32(input) as usize;
-    let bytes = read_bytes(input, size);
+pub fn read_string(input: &[u8], pos: &mut usize) -> Result<String> {
+    let size = read_be_u32(input, pos)? as usize;
+    let offset = *pos;
+    let bytes = read_bytes(input, pos, size)?;
 
-    String::from_utf8(bytes.try_into().unwrap()).unwrap()
+    String::from_utf8(bytes.to_vec()).map_err(|_| GenerateError::InvalidUtf8(offset))
 }
 
 pub fn get_xqffile_manuals() -> Vec<manual::Manual> {
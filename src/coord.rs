@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 use crate::common;
+use serde_derive::{Deserialize, Serialize};
 use std::{
     fmt::{Display, Formatter},
     path::Path,
@@ -22,6 +23,9 @@ pub enum RecordType {
     PgnIccs,
     PgnRc,
     PgnZh,
+    PgnWxf,
+    // 标准PGN：标签对及带回合数、注释、变着的着法文本
+    Pgn,
 }
 
 impl RecordType {
@@ -38,6 +42,8 @@ impl RecordType {
             _ if ext.eq(RecordType::PgnIccs.ext_name().as_str()) => Some(RecordType::PgnIccs),
             _ if ext.eq(RecordType::PgnRc.ext_name().as_str()) => Some(RecordType::PgnRc),
             _ if ext.eq(RecordType::PgnZh.ext_name().as_str()) => Some(RecordType::PgnZh),
+            _ if ext.eq(RecordType::PgnWxf.ext_name().as_str()) => Some(RecordType::PgnWxf),
+            _ if ext.eq(RecordType::Pgn.ext_name().as_str()) => Some(RecordType::Pgn),
             _ => None,
         }
     }
@@ -52,13 +58,13 @@ pub enum ChangeType {
     NoChange,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
 pub struct Coord {
     pub row: usize,
     pub col: usize,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
 pub struct CoordPair {
     pub from_coord: Coord,
     pub to_coord: Coord,
@@ -217,6 +223,13 @@ impl CoordPair {
         Ok(CoordPair::from(from_coord, to_coord))
     }
 
+    pub fn from_index(from_index: usize, to_index: usize) -> common::Result<Self> {
+        let from_coord = Coord::from_index(from_index)?;
+        let to_coord = Coord::from_index(to_index)?;
+
+        Ok(CoordPair::from(from_coord, to_coord))
+    }
+
     pub fn from_string(coordpair_str: &str, record_type: RecordType) -> common::Result<Self> {
         let mid = coordpair_str.len() / 2;
         let from_coord = Coord::from_string(&coordpair_str[..mid], record_type)?;
@@ -0,0 +1,415 @@
+#![allow(dead_code)]
+
+use crate::bit_board::BitBoard;
+use crate::coord::CoordPair;
+use crate::evaluation::{evaluate, KINDVALUES};
+use crate::piece;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// 杀棋分值，超过WINVALUE即为杀棋(含步数)
+pub const MATEVALUE: i32 = 10000;
+pub const WINVALUE: i32 = MATEVALUE - 200;
+
+pub const MAXDEPTH: usize = 64;
+const HASHSIZE: usize = 1 << 16;
+const CHECKNODES: usize = 1 << 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchLimit {
+    Depth(usize),
+    Time(Duration),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HashFlag {
+    Exact,
+    // 分值为上限(未超过alpha)
+    Upper,
+    // 分值为下限(已超过beta)
+    Lower,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HashItem {
+    lock: u64,
+    depth: usize,
+    value: i32,
+    flag: HashFlag,
+    best: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Option<CoordPair>,
+    pub value: i32,
+    pub depth: usize,
+    pub nodes: usize,
+    pub pv: Vec<CoordPair>,
+}
+
+pub struct Engine {
+    hash_items: Vec<Option<HashItem>>,
+
+    nodes: usize,
+    start_time: Instant,
+    time_limit: Option<Duration>,
+    is_stopped: bool,
+    // 外部停止标志，由调用方在搜索前复位
+    stop_flag: Arc<AtomicBool>,
+}
+
+fn to_coordpairs(from_to_indexs: &[(usize, usize)]) -> Vec<CoordPair> {
+    from_to_indexs
+        .iter()
+        .map(|&(from_index, to_index)| CoordPair::from_index(from_index, to_index).unwrap())
+        .collect()
+}
+
+// 杀棋分值存入置换表时转换为与当前层无关的分值
+fn value_to_hash(value: i32, ply: usize) -> i32 {
+    if value >= WINVALUE {
+        value + ply as i32
+    } else if value <= -WINVALUE {
+        value - ply as i32
+    } else {
+        value
+    }
+}
+
+fn value_from_hash(value: i32, ply: usize) -> i32 {
+    if value >= WINVALUE {
+        value - ply as i32
+    } else if value <= -WINVALUE {
+        value + ply as i32
+    } else {
+        value
+    }
+}
+
+impl SearchResult {
+    pub fn new() -> Self {
+        SearchResult {
+            best_move: None,
+            value: 0,
+            depth: 0,
+            nodes: 0,
+            pv: vec![],
+        }
+    }
+
+    pub fn is_mate(&self) -> bool {
+        self.value.abs() >= WINVALUE
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::from(HASHSIZE)
+    }
+
+    // hash_size: 置换表项数，取2的幂
+    pub fn from(hash_size: usize) -> Self {
+        Engine {
+            hash_items: vec![None; hash_size.next_power_of_two()],
+
+            nodes: 0,
+            start_time: Instant::now(),
+            time_limit: None,
+            is_stopped: false,
+            stop_flag: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn get_stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop_flag.clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.hash_items.iter_mut().for_each(|item| *item = None);
+    }
+
+    // 以局面的走棋方搜索，迭代加深直至深度或时间限制
+    pub fn search(&mut self, bit_board: &BitBoard, limit: SearchLimit) -> SearchResult {
+        let mut bit_board = *bit_board;
+        let (max_depth, time_limit) = match limit {
+            SearchLimit::Depth(depth) => (depth.clamp(1, MAXDEPTH), None),
+            SearchLimit::Time(time) => (MAXDEPTH, Some(time)),
+        };
+
+        self.nodes = 0;
+        self.start_time = Instant::now();
+        self.time_limit = time_limit;
+        self.is_stopped = false;
+
+        let mut result = SearchResult::new();
+        for depth in 1..=max_depth {
+            let mut pv = vec![];
+            let value = self.alpha_beta(&mut bit_board, -MATEVALUE, MATEVALUE, depth, 0, &mut pv);
+            // 中断的迭代结果不完整，仅在尚无结果时采用
+            if self.is_stopped && result.best_move.is_some() {
+                break;
+            }
+
+            if !pv.is_empty() {
+                result.best_move = CoordPair::from_index(pv[0].0, pv[0].1).ok();
+                result.value = value;
+                result.depth = depth;
+                result.pv = to_coordpairs(&pv);
+            }
+            result.nodes = self.nodes;
+
+            if self.is_stopped || pv.is_empty() || result.is_mate() {
+                break;
+            }
+        }
+
+        result
+    }
+
+    fn check_stop(&mut self) -> bool {
+        if self.nodes.is_multiple_of(CHECKNODES) {
+            if self.stop_flag.load(Ordering::Relaxed) {
+                self.is_stopped = true;
+            }
+            if let Some(time_limit) = self.time_limit {
+                if self.start_time.elapsed() >= time_limit {
+                    self.is_stopped = true;
+                }
+            }
+        }
+
+        self.is_stopped
+    }
+
+    fn probe_hash(&self, key: u64, lock: u64) -> Option<HashItem> {
+        let item = self.hash_items[key as usize & (self.hash_items.len() - 1)]?;
+        if item.lock == lock {
+            Some(item)
+        } else {
+            None
+        }
+    }
+
+    // 同一局面保留深度更大的记录，不同局面直接覆盖
+    fn record_hash(&mut self, key: u64, item: HashItem) {
+        let index = key as usize & (self.hash_items.len() - 1);
+        match self.hash_items[index] {
+            Some(old_item) if old_item.lock == item.lock && old_item.depth > item.depth => (),
+            _ => self.hash_items[index] = Some(item),
+        }
+    }
+
+    // 置换表着法优先，其次按吃子价值排序
+    fn sort_moves(
+        bit_board: &BitBoard,
+        from_to_indexs: &mut [(usize, usize)],
+        hash_move: Option<(usize, usize)>,
+    ) {
+        let move_score = |from_to_index: &(usize, usize)| {
+            let (from_index, to_index) = *from_to_index;
+            if Some(*from_to_index) == hash_move {
+                return i32::MAX;
+            }
+
+            match bit_board.get_kind(to_index) {
+                piece::Kind::NoKind => 0,
+                eat_kind => {
+                    let kind = bit_board.get_kind(from_index);
+                    (1 << 16) + KINDVALUES[eat_kind as usize] * 10 - KINDVALUES[kind as usize]
+                }
+            }
+        };
+
+        from_to_indexs.sort_by_key(|from_to_index| std::cmp::Reverse(move_score(from_to_index)));
+    }
+
+    fn alpha_beta(
+        &mut self,
+        bit_board: &mut BitBoard,
+        mut alpha: i32,
+        beta: i32,
+        depth: usize,
+        ply: usize,
+        pv: &mut Vec<(usize, usize)>,
+    ) -> i32 {
+        if depth == 0 {
+            return self.quiesce(bit_board, alpha, beta, ply);
+        }
+
+        self.nodes += 1;
+        if self.check_stop() {
+            return 0;
+        }
+
+        let color = bit_board.side_color();
+        let key = bit_board.get_key(color);
+        let lock = bit_board.get_lock(color);
+        let mut hash_move = None;
+        if let Some(item) = self.probe_hash(key, lock) {
+            hash_move = item.best;
+            if ply > 0 && item.depth >= depth {
+                let value = value_from_hash(item.value, ply);
+                let is_cut = match item.flag {
+                    HashFlag::Exact => true,
+                    HashFlag::Lower => value >= beta,
+                    HashFlag::Upper => value <= alpha,
+                };
+                if is_cut {
+                    pv.clear();
+                    pv.extend(item.best);
+                    return value;
+                }
+            }
+        }
+
+        let mut from_to_indexs = bit_board.legal_from_to_indexs(color);
+        // 无子可走：将死或困毙均判负
+        if from_to_indexs.is_empty() {
+            return -MATEVALUE + ply as i32;
+        }
+        Self::sort_moves(bit_board, &mut from_to_indexs, hash_move);
+
+        let mut best_value = -MATEVALUE;
+        let mut best_move = None;
+        let mut flag = HashFlag::Upper;
+        for (from_index, to_index) in from_to_indexs {
+            let eat_kind = bit_board.do_move(from_index, to_index).unwrap();
+            let mut child_pv = vec![];
+            let value = if eat_kind == piece::Kind::King {
+                MATEVALUE - ply as i32 - 1
+            } else {
+                -self.alpha_beta(bit_board, -beta, -alpha, depth - 1, ply + 1, &mut child_pv)
+            };
+            bit_board.undo_move(from_index, to_index, eat_kind);
+            if self.is_stopped {
+                return 0;
+            }
+
+            if value > best_value {
+                best_value = value;
+                best_move = Some((from_index, to_index));
+                if value > alpha {
+                    alpha = value;
+                    flag = HashFlag::Exact;
+                    pv.clear();
+                    pv.push((from_index, to_index));
+                    pv.append(&mut child_pv);
+
+                    if value >= beta {
+                        flag = HashFlag::Lower;
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.record_hash(
+            key,
+            HashItem {
+                lock,
+                depth,
+                value: value_to_hash(best_value, ply),
+                flag,
+                best: best_move,
+            },
+        );
+
+        best_value
+    }
+
+    // 静态搜索：仅搜索吃子着法；被将军时搜索全部应将着法
+    fn quiesce(&mut self, bit_board: &mut BitBoard, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.nodes += 1;
+        if self.check_stop() {
+            return 0;
+        }
+
+        let color = bit_board.side_color();
+        if ply >= MAXDEPTH * 2 {
+            return evaluate(bit_board, color);
+        }
+
+        let is_killed = bit_board.is_killed(color);
+        let mut from_to_indexs = if is_killed {
+            let from_to_indexs = bit_board.legal_from_to_indexs(color);
+            if from_to_indexs.is_empty() {
+                return -MATEVALUE + ply as i32;
+            }
+
+            from_to_indexs
+        } else {
+            let value = evaluate(bit_board, color);
+            if value >= beta {
+                return value;
+            }
+            alpha = alpha.max(value);
+
+            bit_board.get_capture_from_to_indexs(color)
+        };
+        Self::sort_moves(bit_board, &mut from_to_indexs, None);
+
+        let mut best_value = if is_killed { -MATEVALUE } else { alpha };
+        for (from_index, to_index) in from_to_indexs {
+            let eat_kind = bit_board.do_move(from_index, to_index).unwrap();
+            if eat_kind == piece::Kind::King {
+                bit_board.undo_move(from_index, to_index, eat_kind);
+                return MATEVALUE - ply as i32 - 1;
+            }
+
+            let value = if !is_killed && bit_board.is_killed(color) {
+                None
+            } else {
+                Some(-self.quiesce(bit_board, -beta, -alpha, ply + 1))
+            };
+            bit_board.undo_move(from_index, to_index, eat_kind);
+            if self.is_stopped {
+                return 0;
+            }
+
+            if let Some(value) = value {
+                if value > best_value {
+                    best_value = value;
+                    if value > alpha {
+                        alpha = value;
+                        if value >= beta {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        best_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_board::GameState;
+    use crate::board;
+
+    #[test]
+    fn test_engine() {
+        let mut engine = Engine::new();
+        let bit_board = BitBoard::new();
+        let result = engine.search(&bit_board, SearchLimit::Depth(2));
+        assert_eq!(result.depth, 2);
+        assert!(!result.pv.is_empty());
+        assert_eq!(result.best_move, result.pv.first().copied());
+
+        // 一步杀
+        let mut bit_board = BitBoard::from(&board::fen_to_pieces("3k5/8R/9/9/9/R8/9/9/9/4K4"));
+        let result = engine.search(&bit_board, SearchLimit::Depth(3));
+        assert!(result.is_mate() && result.value > 0);
+
+        let (from_index, to_index) = result.best_move.unwrap().from_to_index();
+        bit_board.do_move(from_index, to_index);
+        assert_eq!(bit_board.game_state(), GameState::Checkmated);
+
+        let result = engine.search(&bit_board, SearchLimit::Time(Duration::from_millis(100)));
+        assert_eq!(result.best_move, None);
+    }
+}
@@ -5,14 +5,18 @@
 // use std::cell::RefCell;
 // use rayon::vec;
 use crate::manual;
-use crate::models::ManualInfo;
+use crate::models::{AspectData, EvaluationData, ManualInfo, ZorbistData};
+use crate::piece::{self, KINDCOUNT};
 use crate::schema;
-use crate::{bit_board, bit_constant, coord};
+use crate::{bit_board, bit_constant, book, coord};
+use diesel::prelude::*;
 use diesel::result::Error;
 use diesel::sqlite::SqliteConnection;
+use rayon::prelude::*;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter}; //coord,
+use std::path::Path;
 
 #[derive(Clone, Copy)]
 pub struct Evaluation {
@@ -48,9 +52,120 @@ pub struct FromToIndex {
     eval: Evaluation,
 }
 
+type PositionValueArray = [[i32; coord::SEATCOUNT]; KINDCOUNT];
+
+// 棋子基本分值[kind]
+pub const KINDVALUES: [i32; KINDCOUNT] = [0, 200, 200, 450, 1000, 450, 100];
+
+// 位置分值[kind][index]，以处于底部一方的视角(第9行为本方底线)
+#[rustfmt::skip]
+const POSITIONVALUES: PositionValueArray = [
+    // 帅
+    [
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, -9, -9, -9, 0, 0, 0,
+        0, 0, 0, -8, -8, -8, 0, 0, 0,
+        0, 0, 0, 1, 5, 1, 0, 0, 0,
+    ],
+    // 仕
+    [
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, -1, 0, -1, 0, 0, 0,
+        0, 0, 0, 0, 3, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+    // 相
+    [
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, -1, 0, 0, 0, -1, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        -2, 0, 0, 0, 3, 0, 0, 0, -2,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, -1, 0, 0, 0, -1, 0, 0,
+    ],
+    // 马
+    [
+        4, 8, 16, 12, 4, 12, 16, 8, 4,
+        4, 10, 28, 16, 8, 16, 28, 10, 4,
+        12, 14, 16, 20, 18, 20, 16, 14, 12,
+        8, 24, 18, 24, 20, 24, 18, 24, 8,
+        6, 16, 14, 18, 16, 18, 14, 16, 6,
+        4, 12, 16, 14, 12, 14, 16, 12, 4,
+        2, 6, 8, 6, 10, 6, 8, 6, 2,
+        4, 2, 8, 8, 4, 8, 8, 2, 4,
+        0, 2, 4, 4, -2, 4, 4, 2, 0,
+        0, -4, 0, 0, 0, 0, 0, -4, 0,
+    ],
+    // 车
+    [
+        14, 14, 12, 18, 16, 18, 12, 14, 14,
+        16, 20, 18, 24, 26, 24, 18, 20, 16,
+        12, 12, 12, 18, 18, 18, 12, 12, 12,
+        12, 18, 16, 22, 22, 22, 16, 18, 12,
+        12, 14, 12, 18, 18, 18, 12, 14, 12,
+        12, 16, 14, 20, 20, 20, 14, 16, 12,
+        6, 10, 8, 14, 14, 14, 8, 10, 6,
+        4, 8, 6, 14, 12, 14, 6, 8, 4,
+        8, 4, 8, 16, 8, 16, 8, 4, 8,
+        -2, 10, 6, 14, 12, 14, 6, 10, -2,
+    ],
+    // 炮
+    [
+        6, 4, 0, -10, -12, -10, 0, 4, 6,
+        2, 2, 0, -4, -14, -4, 0, 2, 2,
+        2, 2, 0, -10, -8, -10, 0, 2, 2,
+        0, 0, -2, 4, 10, 4, -2, 0, 0,
+        0, 0, 0, 2, 8, 2, 0, 0, 0,
+        -2, 0, 4, 2, 6, 2, 4, 0, -2,
+        0, 0, 0, 2, 4, 2, 0, 0, 0,
+        4, 0, 8, 6, 10, 6, 8, 0, 4,
+        0, 2, 4, 6, 6, 6, 4, 2, 0,
+        0, 0, 2, 6, 6, 6, 2, 0, 0,
+    ],
+    // 兵
+    [
+        0, 3, 6, 9, 12, 9, 6, 3, 0,
+        18, 36, 56, 80, 120, 80, 56, 36, 18,
+        14, 26, 42, 60, 80, 60, 42, 26, 14,
+        10, 20, 30, 34, 40, 34, 30, 20, 10,
+        6, 12, 18, 18, 20, 18, 18, 12, 6,
+        2, 0, 8, 0, 8, 0, 8, 0, 2,
+        0, 0, -2, 0, 4, 0, -2, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+];
+
+// 每一可走位置的灵活性分值[kind]
+const MOBILITYVALUES: [i32; KINDCOUNT] = [0, 0, 0, 4, 2, 1, 0];
+
+// 对方棋子每攻击一个九宫位置的扣分
+const PALACEATTACKVALUE: i32 = 8;
+
+// 缺少一个仕(相)时，对方每个进攻棋子的扣分
+const GUARDLACKVALUE: i32 = 6;
+
 lazy_static! {
     pub static ref ZORBIST: Zorbist = {
         let conn = &mut crate::models::get_conn();
+        Zorbist::update_db(conn).unwrap();
         Zorbist::from_db(conn).unwrap()
     };
 }
@@ -295,24 +410,136 @@ impl Zorbist {
         result
     }
 
+    // 从manual表中id大于manual_id的棋谱重建，返回结果及其中最大的id
+    pub fn from_manual_db(
+        conn: &mut SqliteConnection,
+        manual_id: i32,
+    ) -> Result<(Self, i32), Error> {
+        let (key_aspects, max_id) = Self::get_key_aspects_from_manual_db(conn, manual_id)?;
+        Ok((Self::from_key_aspects(key_aspects), max_id))
+    }
+
+    // 取得id大于manual_id的棋谱中各局面的原始键(未经碰撞链移位)及着法
+    fn get_key_aspects_from_manual_db(
+        conn: &mut SqliteConnection,
+        manual_id: i32,
+    ) -> Result<(Vec<(u64, Aspect)>, i32), Error> {
+        let id_rowcols = ManualInfo::get_id_rowcols(conn, manual_id)?;
+        let max_id = id_rowcols
+            .iter()
+            .map(|&(id, _)| id)
+            .fold(manual_id, i32::max);
+        let bit_board = bit_board::BitBoard::new();
+        let key_aspects: Vec<(u64, Aspect)> = id_rowcols
+            .par_iter()
+            .filter_map(|(_, rowcols)| rowcols.as_ref())
+            .flat_map_iter(|rowcols| {
+                bit_board
+                    .clone()
+                    .get_key_lock_from_tos(rowcols)
+                    .into_iter()
+                    .map(|(key, lock, from, to)| {
+                        (key, Aspect::from(lock, from, to, Evaluation::from(1)))
+                    })
+            })
+            .collect();
+
+        Ok((key_aspects, max_id))
+    }
+
+    // 从zorbist表载入，不写入数据库；新增的棋谱须先经update_db计入表中
     pub fn from_db(conn: &mut SqliteConnection) -> Result<Self, Error> {
         let mut result = Zorbist::new();
-        let bit_board = bit_board::BitBoard::new();
-        for rowcols in ManualInfo::get_rowcols(conn)? {
-            if let Some(rowcols) = rowcols {
-                for (key, lock, from, to) in bit_board.clone().get_key_lock_from_tos(&rowcols) {
-                    result.insert(key, Aspect::from(lock, from, to, Evaluation::from(1)));
+        for (key, lock, from, to, count) in ZorbistData::load_all(conn)? {
+            // 表中的键已按碰撞链分配，直接存放
+            result
+                .key_aspects
+                .entry(key as u64)
+                .or_insert_with(|| Aspect::new(lock as u64))
+                .insert(FromIndex::from(
+                    from as usize,
+                    vec![ToIndex::from(to as usize, Evaluation::from(count as usize))],
+                ));
+        }
+
+        Ok(result)
+    }
+
+    // 累加存入zorbist表。键须为原始键，由表中的碰撞链分配位置；
+    // 相同键及lock的局面先行合并，以减少数据库操作
+    pub fn save_key_aspects(
+        conn: &mut SqliteConnection,
+        key_aspects: Vec<(u64, Aspect)>,
+    ) -> Result<usize, Error> {
+        let mut key_lock_aspects: HashMap<(u64, u64), Aspect> = HashMap::new();
+        for (key, aspect) in key_aspects {
+            match key_lock_aspects.get_mut(&(key, aspect.lock)) {
+                Some(old_aspect) => {
+                    for from_index in aspect.from_indexs {
+                        old_aspect.insert(from_index);
+                    }
+                }
+                None => {
+                    key_lock_aspects.insert((key, aspect.lock), aspect);
                 }
             }
         }
 
-        Ok(result)
+        conn.transaction(|conn| {
+            let mut count = 0;
+            for ((key, _), aspect) in &key_lock_aspects {
+                let zorbist_id = ZorbistData::get_chain_id(
+                    conn,
+                    *key,
+                    aspect.lock,
+                    &bit_constant::COLLIDEZOBRISTKEY,
+                )?;
+                for from_index in &aspect.from_indexs {
+                    let aspect_id = AspectData::get_id(conn, zorbist_id, from_index.from as i32)?;
+                    for to_index in &from_index.to_indexs {
+                        count += EvaluationData {
+                            to_index: to_index.to as i32,
+                            count: to_index.eval.count as i32,
+                            aspect_id,
+                        }
+                        .save_db(conn)?;
+                    }
+                }
+            }
+
+            Ok(count)
+        })
+    }
+
+    // 将manual表中尚未计入的棋谱增量存入zorbist表
+    pub fn update_db(conn: &mut SqliteConnection) -> Result<usize, Error> {
+        let manual_id = ZorbistData::get_manual_id(conn)?;
+        let (key_aspects, max_id) = Self::get_key_aspects_from_manual_db(conn, manual_id)?;
+        if max_id == manual_id {
+            return Ok(0);
+        }
+
+        let count = Self::save_key_aspects(conn, key_aspects)?;
+        ZorbistData::set_manual_id(conn, max_id)?;
+
+        Ok(count)
     }
 
+    // 各棋谱的局面并行生成后依次存入
     pub fn from_manuals(manuals: &Vec<manual::Manual>) -> Self {
+        let key_aspects: Vec<(u64, Aspect)> = manuals
+            .par_iter()
+            .flat_map_iter(|manual| manual.get_key_aspects())
+            .collect();
+
+        Self::from_key_aspects(key_aspects)
+    }
+
+    // 键须为未经碰撞链移位的原始键，依次存入以保证碰撞链的查找顺序
+    pub fn from_key_aspects(key_aspects: impl IntoIterator<Item = (u64, Aspect)>) -> Self {
         let mut result = Self::new();
-        for manual in manuals {
-            result.append(manual.get_zorbist());
+        for (key, aspect) in key_aspects {
+            result.insert(key, aspect);
         }
 
         result
@@ -326,7 +553,10 @@ impl Zorbist {
                 }
             }
             None => {
-                self.key_aspects.insert(key, aspect);
+                // 碰撞链已满的局面极为罕见，直接舍弃
+                if let Err(Some(key)) = self.get_chain_key(key, aspect.lock) {
+                    self.key_aspects.insert(key, aspect);
+                }
             }
         }
     }
@@ -337,28 +567,289 @@ impl Zorbist {
         }
     }
 
-    pub fn get_mut_aspect(&mut self, mut key: u64, lock: u64) -> Option<&mut Aspect> {
-        for index in 0..bit_constant::COLLIDEZOBRISTKEY.len() {
-            if let Some(aspect) = self.key_aspects.get(&key) {
-                if lock == aspect.lock {
-                    return self.key_aspects.get_mut(&key);
+    // 沿碰撞链查找：返回lock相符的键，或链上第一个空闲的键(链已满时为None)
+    fn get_chain_key(&self, mut key: u64, lock: u64) -> Result<u64, Option<u64>> {
+        for index in 0..=bit_constant::COLLIDEZOBRISTKEY.len() {
+            match self.key_aspects.get(&key) {
+                Some(aspect) if aspect.lock == lock => return Ok(key),
+                Some(_) => (),
+                None => return Err(Some(key)),
+            }
+
+            if index < bit_constant::COLLIDEZOBRISTKEY.len() {
+                key ^= bit_constant::COLLIDEZOBRISTKEY[index];
+            }
+        }
+
+        Err(None)
+    }
+
+    pub fn get_aspect(&self, key: u64, lock: u64) -> Option<&Aspect> {
+        let key = self.get_chain_key(key, lock).ok()?;
+        self.key_aspects.get(&key)
+    }
+
+    pub fn get_mut_aspect(&mut self, key: u64, lock: u64) -> Option<&mut Aspect> {
+        let key = self.get_chain_key(key, lock).ok()?;
+        self.key_aspects.get_mut(&key)
+    }
+
+    // 写入二进制开局库文件，次数作为权重
+    pub fn write_book(&self, path: &Path) -> std::io::Result<()> {
+        let mut entries = vec![];
+        for (&key, aspect) in &self.key_aspects {
+            for from_index in &aspect.from_indexs {
+                for to_index in &from_index.to_indexs {
+                    entries.push(book::BookEntry {
+                        key,
+                        lock: aspect.lock,
+                        from: from_index.from as u8,
+                        to: to_index.to as u8,
+                        weight: to_index.eval.count.min(u32::MAX as usize) as u32,
+                    });
                 }
-            } else {
-                break;
             }
+        }
 
-            assert!(false, "Key:({key:016x})'s Lock({lock:016x}) is not find!\n");
-            key ^= bit_constant::COLLIDEZOBRISTKEY[index];
+        book::Book::write(path, entries)
+    }
+
+    // 局面走棋方的开局库着法及次数，按次数降序
+    pub fn probe(&self, bit_board: &bit_board::BitBoard) -> Vec<(coord::CoordPair, usize)> {
+        let color = bit_board.side_color();
+        match self.get_aspect(bit_board.get_key(color), bit_board.get_lock(color)) {
+            Some(aspect) => aspect
+                .get_from_to_indexs()
+                .iter()
+                .filter_map(|from_to_index| {
+                    coord::CoordPair::from_index(from_to_index.from, from_to_index.to)
+                        .ok()
+                        .map(|coordpair| (coordpair, from_to_index.eval.count))
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    // 次数最多的着法
+    pub fn probe_best(&self, bit_board: &bit_board::BitBoard) -> Option<coord::CoordPair> {
+        self.probe(bit_board)
+            .first()
+            .map(|&(coordpair, _)| coordpair)
+    }
+
+    // 按次数加权随机选取着法，以当前时间为种子
+    pub fn probe_random(&self, bit_board: &bit_board::BitBoard) -> Option<coord::CoordPair> {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+        self.probe_random_seed(bit_board, seed)
+    }
+
+    pub fn probe_random_seed(
+        &self,
+        bit_board: &bit_board::BitBoard,
+        seed: u64,
+    ) -> Option<coord::CoordPair> {
+        let coordpair_counts = self.probe(bit_board);
+        let total: usize = coordpair_counts.iter().map(|&(_, count)| count).sum();
+        if total == 0 {
+            return None;
+        }
+
+        // xorshift种子不能为0
+        let mut value = (bit_constant::xorshift64(seed | 1) % total as u64) as usize;
+        for (coordpair, count) in coordpair_counts {
+            if value < count {
+                return Some(coordpair);
+            }
+            value -= count;
         }
 
         None
     }
 }
 
+fn get_position_value(kind: piece::Kind, index: usize, is_bottom: bool) -> i32 {
+    let index = if is_bottom {
+        index
+    } else {
+        coord::Coord::index_to_change(index, coord::ChangeType::SymmetryV).unwrap()
+    };
+
+    POSITIONVALUES[kind as usize][index]
+}
+
+// 对方进攻棋子攻击九宫，或本方仕相残缺时扣分
+fn get_king_safety_value(bit_board: &bit_board::BitBoard, color: piece::Color) -> i32 {
+    let other_color = piece::other_color(color);
+    let palace = bit_constant::get_kind_put(piece::Kind::King, color == bit_board.bottom_color());
+    let mut attack_count = 0;
+    let mut attacker_count = 0;
+    for kind in [
+        piece::Kind::Knight,
+        piece::Kind::Rook,
+        piece::Kind::Cannon,
+        piece::Kind::Pawn,
+    ] {
+        for index in bit_board.get_indexs_from_color_kind(other_color, kind) {
+            attack_count += (bit_board.get_move_from_index(index) & palace).count_ones() as i32;
+            attacker_count += 1;
+        }
+    }
+
+    let guard_count = (bit_board
+        .get_indexs_from_color_kind(color, piece::Kind::Advisor)
+        .len()
+        + bit_board
+            .get_indexs_from_color_kind(color, piece::Kind::Bishop)
+            .len()) as i32;
+
+    -attack_count * PALACEATTACKVALUE - (4 - guard_count) * attacker_count * GUARDLACKVALUE
+}
+
+fn get_color_value(bit_board: &bit_board::BitBoard, color: piece::Color) -> i32 {
+    let is_bottom = color == bit_board.bottom_color();
+    let mut value = 0;
+    for kind in piece::KINDARRAY {
+        for index in bit_board.get_indexs_from_color_kind(color, kind) {
+            let move_count = bit_board.get_move_from_index(index).count_ones() as i32;
+            value += KINDVALUES[kind as usize]
+                + get_position_value(kind, index, is_bottom)
+                + move_count * MOBILITYVALUES[kind as usize];
+        }
+    }
+
+    value + get_king_safety_value(bit_board, color)
+}
+
+// 局面评估：以color一方的视角，分值越大越有利
+pub fn evaluate(bit_board: &bit_board::BitBoard, color: piece::Color) -> i32 {
+    get_color_value(bit_board, color) - get_color_value(bit_board, piece::other_color(color))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::board;
     use crate::manual;
+    use diesel::connection::SimpleConnection;
+
+    #[test]
+    fn test_evaluate() {
+        let bit_board = bit_board::BitBoard::new();
+        assert_eq!(evaluate(&bit_board, piece::Color::Red), 0);
+
+        for (fen, _, _) in crate::common::FEN_PIECES_CHARS {
+            let mut board = board::Board::from(fen);
+            let value = evaluate(&board.bit_board(), piece::Color::Red);
+            assert_eq!(value, -evaluate(&board.bit_board(), piece::Color::Black));
+
+            // 交换颜色后，分值与原局面另一方相同
+            board.to_change(coord::ChangeType::Exchange);
+            assert_eq!(value, evaluate(&board.bit_board(), piece::Color::Black));
+        }
+
+        // 少一车
+        let bit_board =
+            board::Board::from("rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/1NBAKABNR")
+                .bit_board();
+        assert!(
+            evaluate(&bit_board, piece::Color::Red) < -KINDVALUES[piece::Kind::Rook as usize] / 2
+        );
+    }
+
+    #[test]
+    fn test_probe() {
+        let mut bit_board = bit_board::BitBoard::new();
+        let color = bit_board.side_color();
+        let key = bit_board.get_key(color);
+        let lock = bit_board.get_lock(color);
+        let mut zorbist = Zorbist::new();
+        // 炮二平五3次，马二进三1次
+        zorbist.insert(key, Aspect::from(lock, 70, 67, Evaluation::from(2)));
+        zorbist.insert(key, Aspect::from(lock, 88, 69, Evaluation::from(1)));
+        zorbist.insert(key, Aspect::from(lock, 70, 67, Evaluation::from(1)));
+        // 相同key不同lock的碰撞局面
+        zorbist.insert(key, Aspect::from(!lock, 64, 67, Evaluation::from(5)));
+
+        let cannon = coord::CoordPair::from_index(70, 67).unwrap();
+        let knight = coord::CoordPair::from_index(88, 69).unwrap();
+        assert_eq!(zorbist.probe(&bit_board), vec![(cannon, 3), (knight, 1)]);
+        assert_eq!(zorbist.probe_best(&bit_board), Some(cannon));
+        assert_eq!(zorbist.get_aspect(key, !lock).unwrap().from_indexs.len(), 1);
+        for seed in 0..20 {
+            let coordpair = zorbist.probe_random_seed(&bit_board, seed).unwrap();
+            assert!(coordpair == cannon || coordpair == knight);
+        }
+
+        bit_board.do_move(70, 67);
+        assert!(zorbist.probe(&bit_board).is_empty());
+        assert_eq!(zorbist.probe_random(&bit_board), None);
+
+        // 以原始键依次存入，碰撞局面不因存入顺序而分散在链上多处
+        let zorbist = Zorbist::from_key_aspects([
+            (key, Aspect::from(!lock, 64, 67, Evaluation::from(1))),
+            (key, Aspect::from(lock, 70, 67, Evaluation::from(1))),
+            (key, Aspect::from(!lock, 64, 67, Evaluation::from(1))),
+        ]);
+        assert_eq!(zorbist.key_aspects.len(), 2);
+        let aspect = zorbist.get_aspect(key, !lock).unwrap();
+        assert_eq!(aspect.from_indexs[0].to_indexs[0].eval.count, 2);
+    }
+
+    #[test]
+    fn test_zorbist_db() {
+        let conn = &mut SqliteConnection::establish(":memory:").unwrap();
+        for sql in [
+            include_str!("../migrations/2023-07-30-124010_chess/up.sql"),
+            include_str!("../migrations/2026-10-18-000000_zorbist/up.sql"),
+        ] {
+            conn.batch_execute(sql).unwrap();
+        }
+
+        let mut info = ManualInfo::new();
+        // 炮二平五 马8进7
+        info.rowcols = Some("77740726".to_string());
+        ManualInfo::save_db(&vec![info.get_copy(), info.get_copy()], conn).unwrap();
+
+        // 载入不写入数据库
+        assert!(Zorbist::from_db(conn).unwrap().key_aspects.is_empty());
+        assert_eq!(Zorbist::update_db(conn).unwrap(), 2);
+        let zorbist = Zorbist::from_db(conn).unwrap();
+        assert_eq!(zorbist.key_aspects.len(), 2);
+        let mut bit_board = bit_board::BitBoard::new();
+        let cannon = coord::CoordPair::from_index(70, 67).unwrap();
+        assert_eq!(zorbist.probe(&bit_board), vec![(cannon, 2)]);
+
+        // 增量更新：仅计入新增棋谱
+        info.rowcols = Some("7774".to_string());
+        ManualInfo::save_db(&vec![info], conn).unwrap();
+        assert_eq!(Zorbist::update_db(conn).unwrap(), 1);
+        assert_eq!(Zorbist::update_db(conn).unwrap(), 0);
+
+        let zorbist = Zorbist::from_db(conn).unwrap();
+        assert_eq!(zorbist.probe(&bit_board), vec![(cannon, 3)]);
+        bit_board.do_move(70, 67);
+        assert_eq!(zorbist.probe(&bit_board).len(), 1);
+        assert_eq!(ZorbistData::count(conn).unwrap(), 2);
+
+        // 碰撞链长于2且各批次顺序不同时，同一lock仍只占一行
+        let key = 0x1234;
+        let aspect = |lock| Aspect::from(lock, 70, 67, Evaluation::from(1));
+        Zorbist::save_key_aspects(
+            conn,
+            vec![(key, aspect(1)), (key, aspect(2)), (key, aspect(3))],
+        )
+        .unwrap();
+        Zorbist::save_key_aspects(conn, vec![(key, aspect(3)), (key, aspect(2))]).unwrap();
+        assert_eq!(ZorbistData::count(conn).unwrap(), 5);
+        let zorbist = Zorbist::from_db(conn).unwrap();
+        for (lock, count) in [(1, 1), (2, 2), (3, 2)] {
+            let aspect = zorbist.get_aspect(key, lock).unwrap();
+            assert_eq!(aspect.from_indexs[0].to_indexs[0].eval.count, count);
+        }
+    }
 
     #[test]
     // #[ignore = "从文件提取zorbist后存入数据库"]
@@ -6,12 +6,18 @@ mod amove;
 mod bit_board;
 mod bit_constant;
 mod board;
+pub mod book;
 pub mod common;
 mod coord;
 mod database;
+mod engine;
 mod evaluation;
 pub mod manual;
 mod manual_move;
 pub mod models;
+mod pgn;
 mod piece;
+pub mod position;
+mod rule;
 mod schema;
+pub mod ucci;
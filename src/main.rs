@@ -2,5 +2,5 @@ extern crate chess;
 use std::io;
 
 fn main() -> io::Result<()> {
-    Ok(())
+    chess::ucci::run(io::stdin().lock(), io::stdout())
 }
@@ -1,14 +1,20 @@
 #![allow(dead_code)]
 
+use crate::bit_board::GameState;
 use crate::common;
 use crate::coord::{self, COLCOUNT, ROWCOUNT, SEATCOUNT};
 use crate::evaluation;
 use crate::manual_move;
 use crate::models::ManualInfo;
+use crate::pgn;
+use crate::piece;
+use crate::rule;
 use crate::{board, models};
 use diesel::sqlite::SqliteConnection;
 use encoding::all::GBK;
-use encoding::{DecoderTrap, Encoding};
+use encoding::{DecoderTrap, EncoderTrap, Encoding};
+use rayon::prelude::*;
+use serde_derive::{Deserialize, Serialize};
 use std::borrow::{Borrow, BorrowMut};
 use std::fs::{self, DirEntry};
 use std::io;
@@ -17,7 +23,15 @@ use std::path::{Path, PathBuf};
 // use diesel::prelude::*;
 // use diesel::result::Error;
 
-#[derive(Debug)]
+const WINSTRS: [&str; 4] = ["未知", "红胜", "黑胜", "和棋"];
+
+const XQFHEADSIZE: usize = 1024;
+const XQFPIECENUM: usize = 32;
+// QiziXY设定的棋子顺序
+const XQFPIECECHARS: &str = "RNBAKABNRCCPPPPPrnbakabnrccppppp";
+const XQFTYPESTRS: [&str; 4] = ["全局", "开局", "中局", "残局"];
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Manual {
     info: models::ManualInfo,
     manual_move: manual_move::ManualMove,
@@ -43,6 +57,7 @@ impl Manual {
             match record_type {
                 coord::RecordType::Xqf => Self::from_xqf(path),
                 coord::RecordType::Bin => Self::from_bin(path),
+                coord::RecordType::Pgn => Self::from_pgn(path),
                 _ => Self::from_string(path, record_type),
             }
         } else {
@@ -50,7 +65,20 @@ impl Manual {
         }
     }
 
+    // 导入前检验初始局面的合法性
+    fn check_position(info: &models::ManualInfo) -> common::Result<()> {
+        let position = info
+            .get_position()
+            .map_err(|_| common::GenerateError::InvalidBoard)?;
+        if position.board().validate(position.side_color()).is_empty() {
+            Ok(())
+        } else {
+            Err(common::GenerateError::InvalidBoard)
+        }
+    }
+
     pub fn from_info(info: models::ManualInfo) -> common::Result<Self> {
+        Self::check_position(&info)?;
         let fen = info.get_fen();
         let manual_move = if let Some(manual_move_str) = &info.movestring {
             manual_move::ManualMove::from_string(fen, &manual_move_str, coord::RecordType::Txt)?
@@ -66,7 +94,12 @@ impl Manual {
     pub fn write(&self, path: &Path) -> Result<(), std::io::ErrorKind> {
         if let Some(record_type) = coord::RecordType::get_record_type(path) {
             match record_type {
-                coord::RecordType::Xqf => Err(std::io::ErrorKind::Other),
+                coord::RecordType::Xqf => std::fs::write(path, self.get_xqf_bytes(false))
+                    .map_err(|_| std::io::ErrorKind::Other),
+                coord::RecordType::Pgn => {
+                    std::fs::write(path, self.to_pgn_string(coord::RecordType::PgnZh))
+                        .map_err(|_| std::io::ErrorKind::Other)
+                }
                 coord::RecordType::Bin => {
                     std::fs::write(&path, self.get_bytes()).map_err(|_| std::io::ErrorKind::Other)
                 }
@@ -79,170 +112,242 @@ impl Manual {
     }
 
     fn from_xqf(path: &Path) -> common::Result<Self> {
-        match std::fs::read(path) {
-            Ok(input) => {
-                let mut info = models::ManualInfo::new();
-                //文件标记'XQ'=$5158/版本/加密掩码/ProductId[4], 产品(厂商的产品号)
-                // 棋谱评论员/文件的作者
-                // 32个棋子的原始位置
-                // 加密的钥匙和/棋子布局位置钥匙/棋谱起点钥匙/棋谱终点钥匙
-                // 用单字节坐标表示, 将字节变为十进制, 十位数为X(0-8)个位数为Y(0-9),
-                // 棋盘的左下角为原点(0, 0). 32个棋子的位置从1到32依次为:
-                // 红: 车马相士帅士相马车炮炮兵兵兵兵兵 (位置从右到左, 从下到上)
-                // 黑: 车马象士将士象马车炮炮卒卒卒卒卒 (位置从右到左,
-                // 该谁下 0-红先, 1-黑先/最终结果 0-未知, 1-红胜 2-黑胜, 3-和棋
-                // 从下到上)PlayStepNo[2],
-                // 对局类型(开,中,残等)
-                const PIECENUM: usize = 32;
-                let signature = &input[0..2];
-                // let productid = &byte_vec[4..8];
-                let headqizixy = &input[16..48];
-                // let playstepno = &byte_vec[48..50];
-                // let playnodes = &byte_vec[52..56];
-                // let ptreepos = &byte_vec[56..60];
-                // let reserved1 = &byte_vec[60..64];
-                let headcodea_h = &input[64..80];
-                let titlea = &input[80..144];
-                // let titleb = &byte_vec[144..208];
-                let event = &input[208..272];
-                let date = &input[272..288];
-                let site = &input[288..304];
-                let red = &input[304..320];
-                let black = &input[320..336];
-                let opening = &input[336..400];
-                // let redtime = &byte_vec[400..416];
-                // let blktime = &byte_vec[416..432];
-                // let reservedh = &byte_vec[432..464];
-                let rmkwriter = &input[464..480];
-                let author = &input[480..496]; //, Other[528]{};
-                let version = input[2];
-                let headkeymask = input[3];
-                let headkeyora = input[8];
-                let headkeyorb = input[9];
-                let headkeyorc = input[10];
-                let headkeyord = input[11];
-                let headkeyssum = input[12] as usize;
-                let headkeyxy = input[13] as usize;
-                let headkeyxyf = input[14] as usize;
-                let headkeyxyt = input[15] as usize;
-                // let headwhoplay = byte_vec[50];
-                let headplayresult = input[51] as usize;
-
-                if signature[0] != 0x58 || signature[1] != 0x51 {
-                    assert!(false, "文件标记不符。");
-                }
-                if (headkeyssum + headkeyxy + headkeyxyf + headkeyxyt) % 256 != 0 {
-                    assert!(false, "检查密码校验和不对，不等于0。");
-                }
-                if version > 18 {
-                    assert!(
-                        false,
-                        "这是一个高版本的XQF文件，您需要更高版本的XQStudio来读取这个文件。"
-                    );
-                }
+        let input = std::fs::read(path).map_err(|_| common::GenerateError::ReadFileError)?;
+        Self::from_xqf_bytes(&input)
+    }
 
-                let keyxyf: usize;
-                let keyxyt: usize;
-                let keyrmksize: usize;
-                let mut f32keys = [0; PIECENUM];
-
-                let mut head_qizixy = headqizixy.to_vec();
-                // version <= 10 兼容1.0以前的版本
-                if version <= 10 {
-                    keyrmksize = 0;
-                    keyxyf = 0;
-                    keyxyt = 0;
-                } else {
-                    let keyxy;
-                    let calkey = |bkey, ckey| {
-                        // % 256; // 保持为<256
-                        ((((((bkey * bkey) * 3 + 9) * 3 + 8) * 2 + 1) * 3 + 8) * ckey) as u8
-                            as usize
-                    };
-
-                    keyxy = calkey(headkeyxy, headkeyxy);
-                    keyxyf = calkey(headkeyxyf, keyxy);
-                    keyxyt = calkey(headkeyxyt, keyxyf);
-                    // % 65536
-                    keyrmksize = ((headkeyssum * 256 + headkeyxy) % 32000) + 767;
-                    // 棋子位置循环移动
-                    if version >= 12 {
-                        for (index, qizixy) in headqizixy.iter().enumerate() {
-                            head_qizixy[(index + keyxy + 1) % PIECENUM] = *qizixy;
-                        }
-                    }
-                    for qizixy in &mut head_qizixy {
-                        // 保持为8位无符号整数，<256
-                        *qizixy = (*qizixy as isize - keyxy as isize) as u8;
-                    }
-                }
+    fn from_xqf_bytes(input: &[u8]) -> common::Result<Self> {
+        let mut info = models::ManualInfo::new();
+        //文件标记'XQ'=$5158/版本/加密掩码/ProductId[4], 产品(厂商的产品号)
+        // 棋谱评论员/文件的作者
+        // 32个棋子的原始位置
+        // 加密的钥匙和/棋子布局位置钥匙/棋谱起点钥匙/棋谱终点钥匙
+        // 用单字节坐标表示, 将字节变为十进制, 十位数为X(0-8)个位数为Y(0-9),
+        // 棋盘的左下角为原点(0, 0). 32个棋子的位置从1到32依次为:
+        // 红: 车马相士帅士相马车炮炮兵兵兵兵兵 (位置从右到左, 从下到上)
+        // 黑: 车马象士将士象马车炮炮卒卒卒卒卒 (位置从右到左,
+        // 该谁下 0-红先, 1-黑先/最终结果 0-未知, 1-红胜 2-黑胜, 3-和棋
+        // 从下到上)PlayStepNo[2],
+        // 对局类型(开,中,残等)
+        if input.len() < XQFHEADSIZE {
+            return Err(common::GenerateError::Truncated {
+                offset: 0,
+                size: XQFHEADSIZE,
+            });
+        }
 
-                let keybytes = [
-                    (headkeyssum as u8 & headkeymask) | headkeyora,
-                    (headkeyxy as u8 & headkeymask) | headkeyorb,
-                    (headkeyxyf as u8 & headkeymask) | headkeyorc,
-                    (headkeyxyt as u8 & headkeymask) | headkeyord,
-                ];
-                for (index, ch) in "[(C) Copyright Mr. Dong Shiwei.]".bytes().enumerate() {
-                    f32keys[index] = ch & keybytes[index % 4];
-                } // ord(c)
-
-                // 取得棋子字符串
-                let mut piece_chars = vec![b'_'; SEATCOUNT];
-                // QiziXY设定的棋子顺序
-                for (index, ch) in "RNBAKABNRCCPPPPPrnbakabnrccppppp".bytes().enumerate() {
-                    let xy = head_qizixy[index] as usize;
-                    if xy < SEATCOUNT {
-                        // 用单字节坐标表示, 将字节变为十进制,
-                        // 十位数为X(0-8),个位数为Y(0-9),棋盘的左下角为原点(0, 0)
-                        piece_chars[(ROWCOUNT - 1 - xy % ROWCOUNT) * COLCOUNT + xy / ROWCOUNT] = ch;
-                    }
+        let signature = &input[0..2];
+        // let productid = &byte_vec[4..8];
+        let headqizixy = &input[16..48];
+        // let playstepno = &byte_vec[48..50];
+        // let playnodes = &byte_vec[52..56];
+        // let ptreepos = &byte_vec[56..60];
+        // let reserved1 = &byte_vec[60..64];
+        let headcodea_h = &input[64..80];
+        let titlea = &input[80..144];
+        // let titleb = &byte_vec[144..208];
+        let event = &input[208..272];
+        let date = &input[272..288];
+        let site = &input[288..304];
+        let red = &input[304..320];
+        let black = &input[320..336];
+        let opening = &input[336..400];
+        // let redtime = &byte_vec[400..416];
+        // let blktime = &byte_vec[416..432];
+        // let reservedh = &byte_vec[432..464];
+        let rmkwriter = &input[464..480];
+        let author = &input[480..496]; //, Other[528]{};
+        let version = input[2];
+        let headkeyssum = input[12] as usize;
+        let headkeyxy = input[13] as usize;
+        let headkeyxyf = input[14] as usize;
+        let headkeyxyt = input[15] as usize;
+        let headwhoplay = input[50];
+        let headplayresult = input[51] as usize;
+
+        if signature[0] != 0x58 || signature[1] != 0x51 {
+            // 文件标记不符
+            return Err(common::GenerateError::InvalidSignature);
+        }
+        if (headkeyssum + headkeyxy + headkeyxyf + headkeyxyt) % 256 != 0 {
+            // 检查密码校验和不对，不等于0
+            return Err(common::GenerateError::InvalidChecksum);
+        }
+        if version > 18 {
+            // 这是一个高版本的XQF文件，需要更高版本的XQStudio来读取
+            return Err(common::GenerateError::InvalidVersion(version));
+        }
+
+        let (keyxy, keyxyf, keyxyt, keyrmksize, f32keys) = get_xqf_keys(&input[..16]);
+        let mut head_qizixy = headqizixy.to_vec();
+        // version <= 10 兼容1.0以前的版本
+        if version > 10 {
+            // 棋子位置循环移动
+            if version >= 12 {
+                for (index, qizixy) in headqizixy.iter().enumerate() {
+                    head_qizixy[(index + keyxy + 1) % XQFPIECENUM] = *qizixy;
                 }
+            }
+            for qizixy in &mut head_qizixy {
+                // 保持为8位无符号整数，<256
+                *qizixy = (*qizixy as isize - keyxy as isize) as u8;
+            }
+        }
 
-                let fen = board::piece_chars_to_fen(&String::from_utf8(piece_chars).unwrap());
-                let result = ["未知", "红胜", "黑胜", "和棋"];
-                let typestr = ["全局", "开局", "中局", "残局"];
-                let bytes_to_string = |bytes| {
-                    GBK.decode(bytes, DecoderTrap::Ignore)
-                        .unwrap()
-                        .replace('\0', "")
-                        .trim()
-                        .into()
-                };
-
-                info.fen = Some(format!("{fen} r - - 0 1")); // 可能存在不是红棋先走的情况？
-                info.version = Some(version.to_string());
-                info.win = Some(String::from(result[headplayresult as usize]));
-                info.atype = Some(String::from(typestr[headcodea_h[0] as usize]));
-                info.title = bytes_to_string(titlea);
-                info.game = bytes_to_string(event);
-                info.date = Some(bytes_to_string(date));
-                info.site = Some(bytes_to_string(site));
-                info.red = Some(bytes_to_string(red));
-                info.black = Some(bytes_to_string(black));
-                info.opening = Some(bytes_to_string(opening));
-                info.writer = Some(bytes_to_string(rmkwriter));
-                info.author = Some(bytes_to_string(author));
-
-                let manual_move = manual_move::ManualMove::from_xqf(
-                    &fen, &input, version, keyxyf, keyxyt, keyrmksize, &f32keys,
-                )?;
+        // 取得棋子字符串
+        let mut piece_chars = vec![b'_'; SEATCOUNT];
+        // QiziXY设定的棋子顺序
+        for (index, ch) in XQFPIECECHARS.bytes().enumerate() {
+            let xy = head_qizixy[index] as usize;
+            if xy < SEATCOUNT {
+                // 用单字节坐标表示, 将字节变为十进制,
+                // 十位数为X(0-8),个位数为Y(0-9),棋盘的左下角为原点(0, 0)
+                piece_chars[(ROWCOUNT - 1 - xy % ROWCOUNT) * COLCOUNT + xy / ROWCOUNT] = ch;
+            }
+        }
 
-                Ok(Manual::from(info, manual_move))
+        let fen = board::piece_chars_to_fen(&String::from_utf8(piece_chars).unwrap());
+        let bytes_to_string = |bytes| {
+            GBK.decode(bytes, DecoderTrap::Ignore)
+                .unwrap()
+                .replace('\0', "")
+                .trim()
+                .into()
+        };
+
+        info.version = Some(version.to_string());
+        // 文件头中超出范围的结果及类型取"未知"及"全局"
+        info.win = Some(String::from(
+            *WINSTRS.get(headplayresult).unwrap_or(&WINSTRS[0]),
+        ));
+        info.atype = Some(String::from(
+            *XQFTYPESTRS
+                .get(headcodea_h[0] as usize)
+                .unwrap_or(&XQFTYPESTRS[0]),
+        ));
+        info.title = bytes_to_string(titlea);
+        info.game = bytes_to_string(event);
+        info.date = Some(bytes_to_string(date));
+        info.site = Some(bytes_to_string(site));
+        info.red = Some(bytes_to_string(red));
+        info.black = Some(bytes_to_string(black));
+        info.opening = Some(bytes_to_string(opening));
+        info.writer = Some(bytes_to_string(rmkwriter));
+        info.author = Some(bytes_to_string(author));
+
+        let manual_move = manual_move::ManualMove::from_xqf(
+            &fen, input, version, keyxyf, keyxyt, keyrmksize, &f32keys,
+        )?;
+        // 先走方取首着棋子的颜色，无着法时取文件头的"该谁下"
+        let side_color = manual_move.get_first_color().unwrap_or(match headwhoplay {
+            1 => piece::Color::Black,
+            _ => piece::Color::Red,
+        });
+        let side = match side_color {
+            piece::Color::Red => 'r',
+            piece::Color::Black => 'b',
+        };
+        info.fen = Some(format!("{fen} {side} - - 0 1"));
+        Self::check_position(&info)?;
+        let mut manual = Manual::from(info, manual_move);
+        manual.fill_win();
+
+        Ok(manual)
+    }
+
+    // 版本10不加密，版本18加密
+    pub fn get_xqf_bytes(&self, is_encrypted: bool) -> Vec<u8> {
+        let mut result = vec![0; XQFHEADSIZE];
+        result[0..2].copy_from_slice(b"XQ");
+        if is_encrypted {
+            // 加密掩码及钥匙，钥匙和须为256的倍数
+            let (keyxy, keyxyf, keyxyt) = (0x35u8, 0x6Cu8, 0x91u8);
+            result[2] = 18;
+            result[3] = 0xFF;
+            result[12] = 0u8.wrapping_sub(keyxy.wrapping_add(keyxyf).wrapping_add(keyxyt));
+            result[13..16].copy_from_slice(&[keyxy, keyxyf, keyxyt]);
+        } else {
+            result[2] = 10;
+        }
+        let version = result[2];
+        let (keyxy, keyxyf, keyxyt, keyrmksize, f32keys) = get_xqf_keys(&result[..16]);
+
+        // 不在棋盘上的棋子位置为0xFF
+        let mut qizixy = [u8::MAX; XQFPIECENUM];
+        let pieces = board::fen_to_pieces(self.info.get_fen());
+        for (index, piece) in pieces.iter().enumerate() {
+            let ch = piece.ch() as u8;
+            if let Some(slot) = XQFPIECECHARS
+                .bytes()
+                .enumerate()
+                .position(|(slot, slot_ch)| slot_ch == ch && qizixy[slot] == u8::MAX)
+            {
+                let (row, col) = (index / COLCOUNT, index % COLCOUNT);
+                qizixy[slot] = (col * ROWCOUNT + ROWCOUNT - 1 - row) as u8;
             }
-            Err(_) => Err(common::GenerateError::ReadFileError),
         }
+        if version > 10 {
+            for (index, xy) in result[16..48].iter_mut().enumerate() {
+                *xy = qizixy[(index + keyxy + 1) % XQFPIECENUM].wrapping_add(keyxy as u8);
+            }
+        } else {
+            result[16..48].copy_from_slice(&qizixy);
+        }
+
+        // 该谁下 0-红先, 1-黑先
+        result[50] = self
+            .info
+            .get_position()
+            .map_or(0, |position| position.side_color() as u8);
+        result[51] = WINSTRS
+            .iter()
+            .position(|&win| self.info.win.as_deref() == Some(win))
+            .unwrap_or(0) as u8;
+        result[64] = XQFTYPESTRS
+            .iter()
+            .position(|&atype| self.info.atype.as_deref() == Some(atype))
+            .unwrap_or(0) as u8;
+        let string_field = |string: &Option<String>| string.clone().unwrap_or_default();
+        for (range, string) in [
+            (80..144, self.info.title.clone()),
+            (208..272, self.info.game.clone()),
+            (272..288, string_field(&self.info.date)),
+            (288..304, string_field(&self.info.site)),
+            (304..320, string_field(&self.info.red)),
+            (320..336, string_field(&self.info.black)),
+            (336..400, string_field(&self.info.opening)),
+            (464..480, string_field(&self.info.writer)),
+            (480..496, string_field(&self.info.author)),
+        ] {
+            // 按字符截断以免出现半个汉字
+            let mut bytes = vec![];
+            for ch in string.chars() {
+                let ch_bytes = GBK.encode(&ch.to_string(), EncoderTrap::Replace).unwrap();
+                if bytes.len() + ch_bytes.len() > range.len() {
+                    break;
+                }
+                bytes.extend(ch_bytes);
+            }
+            result[range.start..range.start + bytes.len()].copy_from_slice(&bytes);
+        }
+
+        result.append(
+            &mut self
+                .manual_move
+                .get_xqf_bytes(version, keyxyf, keyxyt, keyrmksize, &f32keys),
+        );
+        result
     }
 
     fn from_bin(path: &Path) -> common::Result<Self> {
         match std::fs::read(path) {
             Ok(input) => {
-                let mut input = input.borrow();
+                let mut pos = 0;
                 let mut key_values = vec![];
-                let info_len = common::read_be_u32(&mut input);
+                let info_len = common::read_be_u32(&input, &mut pos)?;
                 for _ in 0..info_len {
-                    let key = common::read_string(&mut input);
-                    let value = common::read_string(&mut input);
+                    let key = common::read_string(&input, &mut pos)?;
+                    let value = common::read_string(&input, &mut pos)?;
 
                     key_values.push((key, value));
                     // println!("key_value: {key} = {value}");
@@ -251,8 +356,9 @@ impl Manual {
 
                 // let fen = get_fen_old(&info_old);
                 let info = models::ManualInfo::from(key_values);
+                Self::check_position(&info)?;
                 let fen = info.get_fen();
-                let manual_move = manual_move::ManualMove::from_bin(fen, &mut input);
+                let manual_move = manual_move::ManualMove::from_bin(fen, &input, &mut pos)?;
                 Ok(Manual::from(info, manual_move))
             }
             Err(_) => Err(common::GenerateError::ReadFileError),
@@ -272,10 +378,71 @@ impl Manual {
         result
     }
 
+    // 根据主线终局局面判定结果：被将死或困毙的一方负
+    pub fn get_end_win(&self) -> Option<&'static str> {
+        match self.manual_move.get_main_end_state() {
+            (color, GameState::Checkmated | GameState::Stalemated) => Some(match color {
+                piece::Color::Red => WINSTRS[2],
+                piece::Color::Black => WINSTRS[1],
+            }),
+            // 未分胜负时，按重复局面、自然限着及子力不足裁决
+            _ => match self
+                .manual_move
+                .get_main_history(self.info.get_halfmove())
+                .adjudicate()?
+            {
+                rule::Ruling::Draw => Some(WINSTRS[3]),
+                rule::Ruling::Lose(piece::Color::Red) => Some(WINSTRS[2]),
+                rule::Ruling::Lose(piece::Color::Black) => Some(WINSTRS[1]),
+            },
+        }
+    }
+
+    // 结果未知时以终局局面填充
+    pub fn fill_win(&mut self) {
+        if self.info.win.is_none() || self.info.win.as_deref() == Some(WINSTRS[0]) {
+            if let Some(win) = self.get_end_win() {
+                self.info.win = Some(win.to_string());
+            }
+        }
+    }
+
+    // 已记录的结果与终局局面是否相符
+    pub fn check_win(&self) -> bool {
+        match self.get_end_win() {
+            Some(win) => self.info.win.as_deref() == Some(win),
+            None => true,
+        }
+    }
+
+    // 合并起始局面与首局相同的棋谱为一个变着树，各着法记录经过的棋局数
+    pub fn from_merge(manuals: &[Manual]) -> Option<Self> {
+        let (manual_move, count) =
+            manual_move::ManualMove::merge_all(manuals.iter().map(|manual| &manual.manual_move))?;
+        let mut info = models::ManualInfo::new();
+        info.title = format!("{}等{count}局", manuals[0].info.title);
+        info.fen = manuals[0].info.fen.clone();
+
+        Some(Manual::from(info, manual_move))
+    }
+
+    // 游标位于起始局面，走棋方取自FEN
+    pub fn cursor(&self) -> manual_move::GameCursor<'_> {
+        let side_color = self
+            .info
+            .get_position()
+            .map_or(piece::Color::Red, |position| position.side_color());
+        self.manual_move.cursor(side_color)
+    }
+
     pub fn get_zorbist(&self) -> evaluation::Zorbist {
         self.manual_move.get_zorbist()
     }
 
+    pub fn get_key_aspects(&self) -> Vec<(u64, evaluation::Aspect)> {
+        self.manual_move.get_key_aspects()
+    }
+
     fn from_string(path: &Path, record_type: coord::RecordType) -> common::Result<Self> {
         let manual_string =
             std::fs::read_to_string(&path).map_err(|_| common::GenerateError::StringParse)?;
@@ -292,6 +459,7 @@ impl Manual {
         }
 
         let info = models::ManualInfo::from(key_values);
+        Self::check_position(&info)?;
         let fen = info.get_fen();
         // if record_type == coord::RecordType::PgnZh {
         //     println!("info:{:?}\nfen:{}", info, fen);
@@ -301,6 +469,50 @@ impl Manual {
         Ok(Manual::from(info, manual_move))
     }
 
+    // 文件中有多局时取第一局
+    fn from_pgn(path: &Path) -> common::Result<Self> {
+        let pgn_string =
+            std::fs::read_to_string(path).map_err(|_| common::GenerateError::ReadFileError)?;
+        Self::from_pgn_string(&pgn_string)?
+            .into_iter()
+            .next()
+            .ok_or(common::GenerateError::StringParse)
+    }
+
+    pub fn from_pgn_string(pgn_string: &str) -> common::Result<Vec<Self>> {
+        let mut manuals = vec![];
+        for game in pgn::split_games(pgn_string) {
+            let info = models::ManualInfo::from(game.get_key_values());
+            Self::check_position(&info)?;
+            let (manual_move, win) = manual_move::ManualMove::from_pgn(
+                &info.get_position().unwrap_or_default(),
+                &game.movetext,
+                game.get_notation(),
+            )?;
+            let mut manual = Manual::from(info, manual_move);
+            if game.get_tag("Result").is_none() {
+                manual.info.win = win.map(String::from);
+            }
+
+            manuals.push(manual);
+        }
+
+        Ok(manuals)
+    }
+
+    // notation为着法记法：PgnIccs、PgnZh或PgnWxf
+    pub fn to_pgn_string(&self, notation: coord::RecordType) -> String {
+        format!(
+            "{}\n{}",
+            pgn::write_tags(&self.info, notation),
+            self.manual_move.to_pgn(
+                notation,
+                &self.info.get_position().unwrap_or_default(),
+                self.info.win.as_deref()
+            )
+        )
+    }
+
     pub fn set_source_moves(&mut self, file_name: &str) {
         self.info.set_source_moves(
             file_name,
@@ -346,27 +558,80 @@ pub fn read_manuals_from_db(
     Ok(result)
 }
 
-pub fn read_manuals_from_dir(dir: &Path) -> io::Result<Vec<Manual>> {
-    let mut manuals = vec![];
+// 由XQF文件头的前16字节计算钥匙：棋子位置、着法起点、着法终点、注解长度及字节解密钥匙
+fn get_xqf_keys(head: &[u8]) -> (usize, usize, usize, usize, [u8; XQFPIECENUM]) {
+    let version = head[2];
+    let headkeymask = head[3];
+    let headkeyssum = head[12] as usize;
+    let headkeyxy = head[13] as usize;
+    let headkeyxyf = head[14] as usize;
+    let headkeyxyt = head[15] as usize;
+
+    let mut keys = (0, 0, 0, 0);
+    // version <= 10 兼容1.0以前的版本
+    if version > 10 {
+        let calkey = |bkey, ckey| {
+            // % 256; // 保持为<256
+            ((((((bkey * bkey) * 3 + 9) * 3 + 8) * 2 + 1) * 3 + 8) * ckey) as u8 as usize
+        };
+
+        let keyxy = calkey(headkeyxy, headkeyxy);
+        let keyxyf = calkey(headkeyxyf, keyxy);
+        let keyxyt = calkey(headkeyxyt, keyxyf);
+        // % 65536
+        let keyrmksize = ((headkeyssum * 256 + headkeyxy) % 32000) + 767;
+        keys = (keyxy, keyxyf, keyxyt, keyrmksize);
+    }
+
+    let keybytes = [
+        (headkeyssum as u8 & headkeymask) | head[8],
+        (headkeyxy as u8 & headkeymask) | head[9],
+        (headkeyxyf as u8 & headkeymask) | head[10],
+        (headkeyxyt as u8 & headkeymask) | head[11],
+    ];
+    let mut f32keys = [0; XQFPIECENUM];
+    for (index, ch) in "[(C) Copyright Mr. Dong Shiwei.]".bytes().enumerate() {
+        f32keys[index] = ch & keybytes[index % 4];
+    } // ord(c)
+
+    (keys.0, keys.1, keys.2, keys.3, f32keys)
+}
+
+// 递归取得目录下的全部文件，无法读取的子目录略过
+fn get_file_paths(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
     if dir.is_dir() {
         for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
+            let path = entry?.path();
             if path.is_dir() {
-                if let Ok(mut sub_manuals) = read_manuals_from_dir(&path) {
-                    manuals.append(&mut sub_manuals);
+                if let Ok(mut sub_paths) = get_file_paths(&path) {
+                    paths.append(&mut sub_paths);
                 }
             } else {
-                if let Ok(mut manual) = Manual::from_path(&path) {
-                    manual.set_source_moves(path.as_os_str().to_str().unwrap());
-                    manuals.push(manual);
-                } else {
-                    println!("Cannot read: {:?}", &path);
-                }
+                paths.push(path);
             }
         }
     }
 
+    Ok(paths)
+}
+
+// 各文件并行读取，结果保持目录遍历的顺序
+pub fn read_manuals_from_dir(dir: &Path) -> io::Result<Vec<Manual>> {
+    let manuals = get_file_paths(dir)?
+        .par_iter()
+        .filter_map(|path| match Manual::from_path(path) {
+            Ok(mut manual) => {
+                manual.set_source_moves(path.as_os_str().to_str().unwrap());
+                Some(manual)
+            }
+            Err(err) => {
+                println!("Cannot read: {:?}: {err}", path);
+                None
+            }
+        })
+        .collect();
+
     Ok(manuals)
 }
 
@@ -384,6 +649,7 @@ mod tests {
         for (&(file_name, manual_string), manual) in filename_manuals {
             // println!("file_name: {}", file_name);
             assert_eq!(manual_string, manual.to_string());
+            assert!(manual.check_win(), "file_name: {file_name}");
 
             // 输出内容以备查看
             for record_type in [
@@ -392,6 +658,7 @@ mod tests {
                 coord::RecordType::PgnIccs,
                 coord::RecordType::PgnRc,
                 coord::RecordType::PgnZh,
+                coord::RecordType::PgnWxf,
             ] {
                 let full_file_name =
                     format!("tests/output/{}.{}", file_name, record_type.ext_name());
@@ -407,6 +674,152 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_manual_xqf() {
+        for (&(file_name, manual_string), manual) in common::get_filename_manuals() {
+            for is_encrypted in [false, true] {
+                let bytes = manual.get_xqf_bytes(is_encrypted);
+                let mut xqf_manual = Manual::from_xqf_bytes(&bytes).unwrap();
+                assert_eq!(
+                    xqf_manual.info.version.as_deref(),
+                    Some(if is_encrypted { "18" } else { "10" })
+                );
+
+                xqf_manual.info.version = manual.info.version.clone();
+                assert_eq!(
+                    manual_string,
+                    xqf_manual.to_string(),
+                    "file_name: {file_name} is_encrypted: {is_encrypted}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_manual_xqf_head() {
+        let (_, manual) = common::get_filename_manuals().into_iter().next().unwrap();
+        let mut bytes = manual.get_xqf_bytes(false);
+        // 对局结果及棋谱类型字节超出范围
+        bytes[51] = 0xFF;
+        bytes[64] = 0xFF;
+        let xqf_manual = Manual::from_xqf_bytes(&bytes).unwrap();
+        assert_eq!(xqf_manual.info.atype.as_deref(), Some(XQFTYPESTRS[0]));
+        assert!(xqf_manual.info.win.is_some());
+    }
+
+    #[test]
+    fn test_manual_xqf_side() {
+        // 红车将军，须黑方先走
+        let mut info = models::ManualInfo::new();
+        info.fen = Some("4k4/9/9/9/4R4/9/9/9/9/3K5 b - - 0 1".to_string());
+        info.rowcols = Some("0405".to_string());
+        let manual = Manual::from_info(info).unwrap();
+        let mut bytes = manual.get_xqf_bytes(false);
+        assert_eq!(bytes[50], 1);
+
+        // 文件头误标为红先时，以首着棋子的颜色为准
+        bytes[50] = 0;
+        let xqf_manual = Manual::from_xqf_bytes(&bytes).unwrap();
+        assert_eq!(
+            xqf_manual.info.get_position().unwrap().side_color(),
+            piece::Color::Black
+        );
+    }
+
+    #[test]
+    fn test_manual_serde() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Manual>();
+
+        let manuals: Vec<Manual> = common::get_filename_manuals()
+            .into_iter()
+            .map(|(_, manual)| manual)
+            .collect();
+        let json = serde_json::to_string(&manuals).unwrap();
+        let serde_manuals: Vec<Manual> = serde_json::from_str(&json).unwrap();
+        for (manual, serde_manual) in manuals.iter().zip(&serde_manuals) {
+            assert_eq!(manual.to_string(), serde_manual.to_string());
+        }
+        assert!(serde_json::from_str::<Manual>("{}").is_err());
+    }
+
+    #[test]
+    fn test_manual_cursor() {
+        for (_, manual) in common::get_filename_manuals() {
+            let mut cursor = manual.cursor();
+            let root_move = cursor.current_move();
+            let start_board = *cursor.board();
+            let start_fen = cursor.get_fen();
+            let start_key = cursor.get_key();
+            assert!(!cursor.back());
+
+            // 逐一转至各着法，与自根着法重演的局面比较
+            for amove in root_move.get_all_after_moves() {
+                assert!(cursor.goto(&amove));
+                assert!(std::sync::Arc::ptr_eq(&cursor.current_move(), &amove));
+                let board = start_board.to_move(&amove, true);
+                assert_eq!(cursor.board(), &board);
+                let mut bit_board = board.bit_board();
+                bit_board.set_side_color(cursor.side_color());
+                assert_eq!(cursor.get_key(), bit_board.get_key(cursor.side_color()));
+
+                let sibling_count = amove.before().unwrap().after_len();
+                let (_, index) = amove.sibling_index().unwrap();
+                assert_eq!(cursor.to_next_sibling(), index + 1 < sibling_count);
+                assert_eq!(
+                    cursor.to_prev_sibling(),
+                    index + 1 < sibling_count || index > 0
+                );
+            }
+
+            cursor.to_end();
+            assert_eq!(cursor.current_move().after_len(), 0);
+            cursor.to_start();
+            assert!(cursor.is_start());
+            assert_eq!((cursor.get_fen(), cursor.get_key()), (start_fen, start_key));
+            assert!(!cursor.goto(&crate::amove::Move::root()));
+
+            // 另一分支中途遇非法着法而转至失败时，游标保持原着法不变
+            cursor.to_end();
+            let old_move = cursor.current_move();
+            if old_move.is_root() {
+                continue;
+            }
+            let first_coordpair = root_move.after().unwrap()[0].coordpair;
+            let bad_move = root_move
+                .append(first_coordpair, String::new())
+                .append(first_coordpair, String::new());
+            let (old_fen, old_key) = (cursor.get_fen(), cursor.get_key());
+            assert!(!cursor.goto(&bad_move));
+            assert!(std::sync::Arc::ptr_eq(&cursor.current_move(), &old_move));
+            assert_eq!((cursor.get_fen(), cursor.get_key()), (old_fen, old_key));
+        }
+    }
+
+    #[test]
+    fn test_manual_pgn() {
+        let manuals = common::get_xqffile_manuals();
+        for notation in [
+            coord::RecordType::PgnIccs,
+            coord::RecordType::PgnZh,
+            coord::RecordType::PgnWxf,
+        ] {
+            let pgn_string = manuals
+                .iter()
+                .map(|manual| manual.to_pgn_string(notation))
+                .collect::<Vec<String>>()
+                .join("\n");
+            let pgn_manuals = Manual::from_pgn_string(&pgn_string).unwrap();
+            assert_eq!(pgn_manuals.len(), manuals.len());
+            for (manual, pgn_manual) in manuals.iter().zip(pgn_manuals.iter()) {
+                assert_eq!(manual, pgn_manual, "notation: {notation:?}");
+                assert_eq!(manual.info.title, pgn_manual.info.title);
+                assert_eq!(manual.info.win, pgn_manual.info.win);
+                assert_eq!(manual.info.get_fen(), pgn_manual.info.get_fen());
+            }
+        }
+    }
+
     #[test]
     #[ignore = "从样板文件提取manual后存入数据库。"]
     fn test_manual_from_file_to_db() {
@@ -1,13 +1,20 @@
 #![allow(dead_code)]
 
+use crate::bit_board;
 use crate::board;
 use crate::coord::CoordPair;
 use crate::evaluation;
+use crate::pgn;
+use crate::piece;
+use crate::position::Position;
+use crate::rule;
 use crate::{amove, common, coord};
 use encoding::all::GBK;
-use encoding::{DecoderTrap, Encoding};
+use encoding::{DecoderTrap, EncoderTrap, Encoding};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_derive::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::rc::Rc;
+use std::sync::Arc;
 // use crate::bit_constant;
 // use std::borrow::Borrow;
 // use crate::utility;
@@ -19,7 +26,66 @@ use std::rc::Rc;
 #[derive(Debug)]
 pub struct ManualMove {
     board: board::Board,
-    root_move: Rc<amove::Move>,
+    root_move: Arc<amove::Move>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MoveStats {
+    pub move_count: usize,
+    pub max_depth: usize,
+    // 非主线的分支数
+    pub variation_count: usize,
+    pub remark_count: usize,
+    pub leaf_count: usize,
+}
+
+// 着法以自根着法起的行列序列(PgnRc)表示
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MoveDiff {
+    // 主线首个不同的半回合序号(自1起算)，起始局面不同时为0，相同时为None
+    pub first_divergent_ply: Option<usize>,
+    // 仅在一方的着法，其后续着法不再列出
+    pub only_self: Vec<String>,
+    pub only_other: Vec<String>,
+    // 共有着法的注解变化：(着法，本方注解，对方注解)
+    pub remark_changes: Vec<(String, String, String)>,
+}
+
+impl MoveDiff {
+    pub fn is_same(&self) -> bool {
+        *self == MoveDiff::default()
+    }
+}
+
+// 序列化时以棋盘FEN及平面着法序列表示
+#[derive(Deserialize, Serialize)]
+struct ManualMoveData {
+    fen: String,
+    moves: Vec<amove::MoveNode>,
+}
+
+impl Serialize for ManualMove {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ManualMoveData {
+            fen: self.board.get_fen(),
+            moves: self.root_move.to_nodes(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ManualMove {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = ManualMoveData::deserialize(deserializer)?;
+        let position = Position::from(&data.fen).map_err(de::Error::custom)?;
+        let root_move = amove::Move::from_nodes(&data.moves)
+            .ok_or_else(|| de::Error::custom("invalid move node order."))?;
+
+        Ok(ManualMove {
+            board: position.board(),
+            root_move,
+        })
+    }
 }
 
 impl PartialEq for ManualMove {
@@ -34,7 +100,11 @@ impl ManualMove {
         ManualMove::from(board::FEN, amove::Move::root())
     }
 
-    fn from(fen: &str, root_move: Rc<amove::Move>) -> Self {
+    fn new_fen(fen: &str) -> Self {
+        ManualMove::from(fen, amove::Move::root())
+    }
+
+    fn from(fen: &str, root_move: Arc<amove::Move>) -> Self {
         ManualMove {
             board: board::Board::from(fen),
             root_move,
@@ -54,7 +124,7 @@ impl ManualMove {
 
     pub fn from_xqf(
         fen: &str,
-        input: &Vec<u8>,
+        input: &[u8],
         version: u8,
         keyxyf: usize,
         keyxyt: usize,
@@ -65,7 +135,10 @@ impl ManualMove {
 
         let read_bytes = |pos: &mut usize, size| {
             let new_pos = *pos + size;
-            let mut bytes = input[*pos..new_pos].to_vec();
+            let mut bytes = input
+                .get(*pos..new_pos)
+                .ok_or(common::GenerateError::Truncated { offset: *pos, size })?
+                .to_vec();
             if version > 10 {
                 // '字节解密'
                 for (index, abyte) in bytes.iter_mut().enumerate() {
@@ -74,31 +147,34 @@ impl ManualMove {
             }
 
             *pos = new_pos;
-            bytes
+            Ok(bytes)
         };
 
         let get_remark_size = |pos: &mut usize| {
-            let data = read_bytes(pos, std::mem::size_of::<u32>());
-            u32::from_le_bytes(data.try_into().unwrap()) as usize - keyrmksize
+            let offset = *pos;
+            let data = read_bytes(pos, std::mem::size_of::<u32>())?;
+            (u32::from_le_bytes(data.try_into().unwrap()) as usize)
+                .checked_sub(keyrmksize)
+                .ok_or(common::GenerateError::InvalidRemarkSize(offset))
         };
 
         let get_data_remark = |pos: &mut usize| {
             const DATASIZE: usize = 4;
-            let mut data = read_bytes(pos, DATASIZE);
+            let mut data = read_bytes(pos, DATASIZE)?;
             let mut remark_size = 0;
             if version <= 10 {
                 data[2] = (if data[2] & 0xF0 != 0 { 0x80 } else { 0 })
                     | (if data[2] & 0x0F != 0 { 0x40 } else { 0 });
-                remark_size = get_remark_size(pos);
+                remark_size = get_remark_size(pos)?;
             } else {
                 data[2] &= 0xE0;
                 if data[2] & 0x20 != 0 {
-                    remark_size = get_remark_size(pos);
+                    remark_size = get_remark_size(pos)?;
                 }
             }
 
             let remark = if remark_size > 0 {
-                GBK.decode(&read_bytes(pos, remark_size), DecoderTrap::Ignore)
+                GBK.decode(&read_bytes(pos, remark_size)?, DecoderTrap::Ignore)
                     .unwrap()
                     .replace("\r\n", "\n")
                     .trim()
@@ -107,27 +183,31 @@ impl ManualMove {
                 String::new()
             };
 
-            (data, remark)
+            Ok((data, remark))
         };
 
         let mut pos: usize = 1024;
         let root_move = amove::Move::root();
-        let (data, remark) = get_data_remark(&mut pos);
+        let (data, remark) = get_data_remark(&mut pos)?;
         root_move.set_remark(remark);
 
+        let board = board::Board::from(fen);
+        let mut number = 0;
         if data[2] & 0x80 != 0 {
             let mut before_moves = vec![root_move.clone()];
             let mut before_move = root_move.clone();
             let mut is_other = false;
             // 当前棋子非根，或为根尚无后续棋子/当前棋子为根，且有后继棋子时，表明深度搜索已经回退到根，已经没有后续棋子了
             while pos < input.len() && (!before_move.is_root() || before_move.after_len() == 0) {
-                let (data, remark) = get_data_remark(&mut pos);
+                let offset = pos;
+                number += 1;
+                let (data, remark) =
+                    get_data_remark(&mut pos).map_err(|err| err.at_move(offset, number))?;
                 //# 一步棋的起点和终点有简单的加密计算，读入时需要还原
                 let fcolrow = __sub(data[0], (0x18 + keyxyf as usize) as u8);
                 let tcolrow = __sub(data[1], (0x20 + keyxyt as usize) as u8);
                 if fcolrow > 89 || tcolrow > 89 {
-                    // assert!(false, "fcolrow > 89 || tcolrow > 89 ? ");
-                    return Err(common::GenerateError::IndexOut);
+                    return Err(common::GenerateError::IndexOut.at_move(offset, number));
                 }
 
                 let frow = (10 - 1 - fcolrow % 10) as usize;
@@ -151,6 +231,19 @@ impl ManualMove {
                 }
 
                 before_move = before_move.append(coord_pair, remark);
+                let (from_index, to_index) = coord_pair.from_to_index();
+                if !board
+                    .to_move(&before_move, false)
+                    .bit_board()
+                    .is_valid(from_index, to_index)
+                {
+                    return Err(common::GenerateError::IllegalMove {
+                        from_index,
+                        to_index,
+                    }
+                    .at_move(offset, number));
+                }
+
                 if has_next && has_other {
                     before_moves.push(before_move.clone());
                 }
@@ -163,38 +256,88 @@ impl ManualMove {
             }
         }
 
-        // List<Move> allMoves = RootMove.AllAfterMoves;
-        // allMoves.Insert(0, RootMove);
-        // allMoves.ForEach(move
-        //     => move.AfterMoves?.RemoveAll(move
-        //         => !GetBoardWith(move.Before).BitBoard.CanMove(move.CoordPair)));
-        let board = board::Board::from(fen);
-        for amove in &root_move.get_all_after_moves() {
-            let (from_index, to_index) = amove.coordpair.from_to_index();
-            let is_valid = board
-                .to_move(amove, false)
-                .bit_board()
-                .is_valid(from_index, to_index);
-            assert!(is_valid, "({from_index}, {to_index}) is invalid!");
-        }
-        
         Ok(ManualMove::from(fen, root_move))
     }
 
-    pub fn from_bin(fen: &str, input: &mut &[u8]) -> Self {
+    // 与from_xqf对应，自文件头之后先序遍历输出着法
+    pub fn get_xqf_bytes(
+        &self,
+        version: u8,
+        keyxyf: usize,
+        keyxyt: usize,
+        keyrmksize: usize,
+        f32keys: &[u8],
+    ) -> Vec<u8> {
+        let mut result = vec![];
+        let mut move_other_stack = vec![(self.root_move.clone(), false)];
+        while let Some((amove, has_other)) = move_other_stack.pop() {
+            let has_next = amove.after_len() > 0;
+            let remark = GBK
+                .encode(&amove.remark().replace('\n', "\r\n"), EncoderTrap::Replace)
+                .unwrap();
+            let mut data = [0; 4];
+            if !amove.is_root() {
+                //# 一步棋的起点和终点有简单的加密计算
+                let (frow, fcol, trow, tcol) = amove.coordpair.row_col();
+                data[0] = (fcol * 10 + 10 - 1 - frow + 0x18 + keyxyf) as u8;
+                data[1] = (tcol * 10 + 10 - 1 - trow + 0x20 + keyxyt) as u8;
+            }
+
+            let has_remark_size = if version <= 10 {
+                data[2] = (if has_next { 0xF0 } else { 0 }) | (if has_other { 0x0F } else { 0 });
+                true
+            } else {
+                data[2] = (if has_next { 0x80 } else { 0 })
+                    | (if has_other { 0x40 } else { 0 })
+                    | (if remark.is_empty() { 0 } else { 0x20 });
+                !remark.is_empty()
+            };
+            result.extend(data);
+            if has_remark_size {
+                result.extend(((remark.len() + keyrmksize) as u32).to_le_bytes());
+                result.extend(remark);
+            }
+
+            if let Some(after) = amove.after() {
+                let after_len = after.len();
+                for (index, bmove) in after.into_iter().enumerate().rev() {
+                    move_other_stack.push((bmove, index + 1 < after_len));
+                }
+            }
+        }
+
+        if version > 10 {
+            // '字节加密'，文件头长度为1024
+            for (index, abyte) in result.iter_mut().enumerate() {
+                *abyte = abyte.wrapping_add(f32keys[(1024 + index) % 32]);
+            }
+        }
+
+        result
+    }
+
+    pub fn from_bin(fen: &str, input: &[u8], pos: &mut usize) -> common::Result<Self> {
         let root_move = amove::Move::root();
-        let remark = common::read_string(input);
-        let after_num = common::read_be_u32(input) as usize;
+        let remark = common::read_string(input, pos)?;
+        let after_num = common::read_be_u32(input, pos)? as usize;
         root_move.set_remark(remark);
 
-        let mut move_after_num_deque: VecDeque<(Rc<amove::Move>, usize)> = VecDeque::new();
+        let mut number = 0;
+        let mut move_after_num_deque: VecDeque<(Arc<amove::Move>, usize)> = VecDeque::new();
         move_after_num_deque.push_back((root_move.clone(), after_num));
         while move_after_num_deque.len() > 0 {
             let (before_move, before_after_num) = move_after_num_deque.pop_front().unwrap();
             for _ in 0..before_after_num {
-                let coordpair = common::read_coordpair(input);
-                let remark = common::read_string(input);
-                let after_num = common::read_be_u32(input) as usize;
+                let offset = *pos;
+                number += 1;
+                let mut read_move = || -> common::Result<_> {
+                    let coordpair = common::read_coordpair(input, pos)?;
+                    let remark = common::read_string(input, pos)?;
+                    let after_num = common::read_be_u32(input, pos)? as usize;
+                    Ok((coordpair, remark, after_num))
+                };
+                let (coordpair, remark, after_num) =
+                    read_move().map_err(|err| err.at_move(offset, number))?;
 
                 let amove = before_move.append(coordpair, remark);
                 if after_num > 0 {
@@ -203,7 +346,7 @@ impl ManualMove {
             }
         }
 
-        ManualMove::from(fen, root_move)
+        Ok(ManualMove::from(fen, root_move))
     }
 
     pub fn get_bytes(&self) -> Vec<u8> {
@@ -227,10 +370,12 @@ impl ManualMove {
         record_type: coord::RecordType,
     ) -> common::Result<Self> {
         let pgnzh_pattern = board::Board::get_pgnzh_pattern();
+        let pgnwxf_pattern = board::Board::get_pgnwxf_pattern();
         let pgn_pattern = match record_type {
             coord::RecordType::PgnRc => r"\d{4}",
             coord::RecordType::PgnIccs => r"(?:[A-I]\d){2}",
             coord::RecordType::PgnZh => pgnzh_pattern.as_str(),
+            coord::RecordType::PgnWxf => pgnwxf_pattern.as_str(),
             _ => r"(?:\(\d,\d\)){2}",
         };
         let remark_num_pattern = r"(?:\{([\s\S]+?)\})?(?:\((\d+)\))?\n";
@@ -241,6 +386,7 @@ impl ManualMove {
 
         let board = board::Board::from(fen);
         let root_move = amove::Move::root();
+        let mut number = 0;
         if let Some(root_caps) = root_move_re.captures(manual_move_str) {
             if let Some(remark) = root_caps.at(1) {
                 root_move.set_remark(remark.to_string());
@@ -248,7 +394,7 @@ impl ManualMove {
 
             if let Some(after_num_str) = root_caps.at(2) {
                 if let Ok(root_after_num) = after_num_str.parse() {
-                    let mut move_after_num_deque: VecDeque<(Rc<amove::Move>, usize)> =
+                    let mut move_after_num_deque: VecDeque<(Arc<amove::Move>, usize)> =
                         VecDeque::new();
                     move_after_num_deque.push_back((root_move.clone(), root_after_num));
                     let mut caps_iter = amove_re.captures_iter(manual_move_str);
@@ -256,18 +402,26 @@ impl ManualMove {
                         let (before_move, before_after_num) =
                             move_after_num_deque.pop_front().unwrap();
                         let the_board = board.to_move(&before_move, true);
+                        // 默认红方先走
+                        let color = before_move.after_color(piece::Color::Red);
                         for _ in 0..before_after_num {
-                            let caps =
-                                caps_iter.next().ok_or(common::GenerateError::StringParse)?;
+                            number += 1;
+                            let caps = caps_iter.next().ok_or(
+                                common::GenerateError::StringParse
+                                    .at_move(manual_move_str.len(), number),
+                            )?;
                             let coordpair_str = caps.at(1).unwrap();
+                            let offset = caps.pos(1).unwrap().0;
                             let coordpair = match record_type {
                                 coord::RecordType::PgnZh => {
-                                    // println!("{:?}\nfen:{}", the_board, fen);
-                                    // println!("{} ", coordpair_str);
-                                    the_board.get_coordpair_from_zhstr(coordpair_str)
+                                    the_board.get_coordpair_from_zhstr(coordpair_str, color)
                                 }
-                                _ => CoordPair::from_string(coordpair_str, record_type)?,
-                            };
+                                coord::RecordType::PgnWxf => {
+                                    the_board.get_coordpair_from_wxfstr(coordpair_str, color)
+                                }
+                                _ => CoordPair::from_string(coordpair_str, record_type),
+                            }
+                            .map_err(|err| err.at_move(offset, number))?;
                             let remark = if let Some(remark) = caps.at(2) {
                                 remark.to_string()
                             } else {
@@ -295,16 +449,172 @@ impl ManualMove {
         })
     }
 
-    pub fn get_zorbist(&self) -> evaluation::Zorbist {
-        let mut zorbist = evaluation::Zorbist::new();
+    // 返回着法文本中的结果标记
+    pub fn from_pgn(
+        position: &Position,
+        movetext: &str,
+        notation: coord::RecordType,
+    ) -> common::Result<(Self, Option<&'static str>)> {
+        let manual_move = ManualMove::new_fen(&position.board().get_fen());
+        let win = pgn::read_moves(
+            &manual_move.board,
+            &manual_move.root_move,
+            movetext,
+            notation,
+            position.side_color(),
+        )?;
+
+        Ok((manual_move, win))
+    }
+
+    pub fn to_pgn(
+        &self,
+        notation: coord::RecordType,
+        position: &Position,
+        win: Option<&str>,
+    ) -> String {
+        pgn::write_moves(&self.board, &self.root_move, notation, position, win)
+    }
+
+    pub fn stats(&self) -> MoveStats {
+        let mut stats = MoveStats {
+            remark_count: usize::from(!self.root_move.remark().is_empty()),
+            ..MoveStats::default()
+        };
         for amove in self.root_move.get_all_after_moves() {
-            let mut bit_board = self.board.to_move(&amove, false).bit_board();
-            if let Some((key, aspect)) = bit_board.get_key_asp_amove(&amove) {
-                zorbist.insert(key, aspect);
+            let after_len = amove.after_len();
+            stats.move_count += 1;
+            stats.max_depth = stats.max_depth.max(amove.before_moves(true).len());
+            stats.variation_count += after_len.saturating_sub(1);
+            stats.remark_count += usize::from(!amove.remark().is_empty());
+            stats.leaf_count += usize::from(after_len == 0);
+        }
+        stats.variation_count += self.root_move.after_len().saturating_sub(1);
+
+        stats
+    }
+
+    pub fn diff(&self, other: &ManualMove) -> MoveDiff {
+        fn get_rowcols(amove: &Arc<amove::Move>) -> String {
+            amove
+                .before_moves(true)
+                .iter()
+                .map(|amove| amove.coordpair.to_string(coord::RecordType::PgnRc))
+                .collect()
+        }
+
+        fn diff_after(amove: &Arc<amove::Move>, bmove: &Arc<amove::Move>, diff: &mut MoveDiff) {
+            if amove.remark() != bmove.remark() {
+                diff.remark_changes
+                    .push((get_rowcols(amove), amove.remark(), bmove.remark()));
+            }
+
+            let a_after = amove.after().unwrap_or_default();
+            let b_after = bmove.after().unwrap_or_default();
+            let find = |moves: &[Arc<amove::Move>], amove: &Arc<amove::Move>| {
+                moves
+                    .iter()
+                    .find(|bmove| bmove.coordpair == amove.coordpair)
+                    .cloned()
+            };
+            for amove in &a_after {
+                match find(&b_after, amove) {
+                    Some(bmove) => diff_after(amove, &bmove, diff),
+                    None => diff.only_self.push(get_rowcols(amove)),
+                }
+            }
+            for bmove in &b_after {
+                if find(&a_after, bmove).is_none() {
+                    diff.only_other.push(get_rowcols(bmove));
+                }
+            }
+        }
+
+        let mut diff = MoveDiff::default();
+        if self.board != other.board {
+            diff.first_divergent_ply = Some(0);
+            return diff;
+        }
+
+        let (mut amove, mut bmove) = (self.root_move.clone(), other.root_move.clone());
+        let mut ply = 0;
+        loop {
+            ply += 1;
+            let a_first = amove.after().and_then(|after| after.first().cloned());
+            let b_first = bmove.after().and_then(|after| after.first().cloned());
+            match (a_first, b_first) {
+                (None, None) => break,
+                (Some(a_first), Some(b_first)) if a_first.coordpair == b_first.coordpair => {
+                    (amove, bmove) = (a_first, b_first);
+                }
+                _ => {
+                    diff.first_divergent_ply = Some(ply);
+                    break;
+                }
             }
         }
+        diff_after(&self.root_move, &other.root_move, &mut diff);
 
-        zorbist
+        diff
+    }
+
+    // 起始局面不同时不合并
+    pub fn merge(&mut self, other: &ManualMove) -> bool {
+        if self.board != other.board {
+            return false;
+        }
+
+        self.root_move.merge(&other.root_move);
+        true
+    }
+
+    // 以首个棋谱的起始局面为准，合并起始局面相同的全部棋谱，返回结果及合并的棋谱数
+    pub fn merge_all<'a>(
+        manual_moves: impl IntoIterator<Item = &'a ManualMove>,
+    ) -> Option<(Self, usize)> {
+        let mut manual_moves = manual_moves.into_iter();
+        let first = manual_moves.next()?;
+        let mut result = ManualMove {
+            board: first.board,
+            root_move: amove::Move::from_nodes(&first.root_move.to_nodes())?,
+        };
+        let mut count = 1;
+        for manual_move in manual_moves {
+            if result.merge(manual_move) {
+                count += 1;
+            }
+        }
+
+        Some((result, count))
+    }
+
+    // 游标位于根着法，first_color为首着的走棋方
+    pub fn cursor(&self, first_color: piece::Color) -> GameCursor<'_> {
+        let mut bit_board = self.board.bit_board();
+        bit_board.set_side_color(first_color);
+
+        GameCursor {
+            manual_move: self,
+            current_move: self.root_move.clone(),
+            board: self.board,
+            bit_board,
+            eat_pieces: vec![],
+        }
+    }
+
+    pub fn get_zorbist(&self) -> evaluation::Zorbist {
+        evaluation::Zorbist::from_key_aspects(self.get_key_aspects())
+    }
+
+    pub fn get_key_aspects(&self) -> Vec<(u64, evaluation::Aspect)> {
+        self.root_move
+            .get_all_after_moves()
+            .iter()
+            .filter_map(|amove| {
+                let mut bit_board = self.board.to_move(amove, false).bit_board();
+                bit_board.get_key_asp_amove(amove)
+            })
+            .collect()
     }
 
     pub fn get_rowcols(&self) -> String {
@@ -319,13 +629,49 @@ impl ManualMove {
         reslut
     }
 
+    // 首着棋子的颜色即先走方，无着法时为None
+    pub fn get_first_color(&self) -> Option<piece::Color> {
+        let first_move = self.root_move.after()?.first()?.clone();
+        self.board
+            .bit_board()
+            .get_color(first_move.coordpair.from_coord.index())
+    }
+
+    // 主线终局局面的走棋方及状态(默认红方先走)
+    pub fn get_main_end_state(&self) -> (piece::Color, bit_board::GameState) {
+        let mut color = piece::Color::Red;
+        let mut amove = self.root_move.clone();
+        while let Some(after) = amove.after() {
+            amove = after.first().unwrap().clone();
+            color = piece::other_color(color);
+        }
+
+        (color, self.board.to_move(&amove, true).game_state(color))
+    }
+
+    // 重放主线着法的对局记录(默认红方先走)，遇不合法着法即停止
+    pub fn get_main_history(&self, halfmove: usize) -> rule::GameHistory {
+        let mut history = rule::GameHistory::from_halfmove(self.board.bit_board(), halfmove);
+        let mut amove = self.root_move.clone();
+        while let Some(after) = amove.after() {
+            amove = after.first().unwrap().clone();
+            let (from_index, to_index) = amove.coordpair.from_to_index();
+            if history.do_move(from_index, to_index).is_none() {
+                break;
+            }
+        }
+
+        history
+    }
+
     pub fn get_coordpairs_from_rowcols(rowcols: &str) -> common::Result<Vec<coord::CoordPair>> {
         let mut coordpairs = vec![];
         for index in 0..(rowcols.len() / 4) {
-            let coordpair = CoordPair::from_string(
-                &rowcols[index * 4..(index + 1) * 4],
-                coord::RecordType::PgnRc,
-            )?;
+            let coordpair = rowcols
+                .get(index * 4..(index + 1) * 4)
+                .ok_or(common::GenerateError::StringParse)
+                .and_then(|rowcol| CoordPair::from_string(rowcol, coord::RecordType::PgnRc))
+                .map_err(|err| err.at_move(index * 4, index + 1))?;
             coordpairs.push(coordpair);
         }
 
@@ -342,6 +688,164 @@ impl ManualMove {
     }
 }
 
+// 在着法树上移动的游标，逐着走动或退回以保持当前局面，无需每次自根着法重演
+pub struct GameCursor<'a> {
+    manual_move: &'a ManualMove,
+    current_move: Arc<amove::Move>,
+    board: board::Board,
+    bit_board: bit_board::BitBoard,
+    // 自根着法至当前着法各着所吃的棋子
+    eat_pieces: Vec<(piece::Piece, piece::Kind)>,
+}
+
+// to_*为游标移动，并非类型转换
+#[allow(clippy::wrong_self_convention)]
+impl<'a> GameCursor<'a> {
+    pub fn current_move(&self) -> Arc<amove::Move> {
+        self.current_move.clone()
+    }
+
+    pub fn board(&self) -> &board::Board {
+        &self.board
+    }
+
+    pub fn bit_board(&self) -> &bit_board::BitBoard {
+        &self.bit_board
+    }
+
+    pub fn side_color(&self) -> piece::Color {
+        self.bit_board.side_color()
+    }
+
+    pub fn get_fen(&self) -> String {
+        format!(
+            "{} {}",
+            self.board.get_fen(),
+            match self.side_color() {
+                piece::Color::Red => 'w',
+                piece::Color::Black => 'b',
+            }
+        )
+    }
+
+    pub fn get_key(&self) -> u64 {
+        self.bit_board.get_key(self.side_color())
+    }
+
+    pub fn is_start(&self) -> bool {
+        self.current_move.is_root()
+    }
+
+    // 走至第variation个后续着法，0为主线
+    pub fn forward(&mut self, variation: usize) -> bool {
+        let amove = match self.current_move.after() {
+            Some(after) if variation < after.len() => after[variation].clone(),
+            _ => return false,
+        };
+
+        let (from_index, to_index) = amove.coordpair.from_to_index();
+        match self.bit_board.do_move(from_index, to_index) {
+            Some(eat_kind) => {
+                let eat_piece = self.board.do_move(&amove);
+                self.eat_pieces.push((eat_piece, eat_kind));
+                self.current_move = amove;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn back(&mut self) -> bool {
+        match self.eat_pieces.pop() {
+            Some((eat_piece, eat_kind)) => {
+                let amove = self.current_move.clone();
+                let (from_index, to_index) = amove.coordpair.from_to_index();
+                self.bit_board.undo_move(from_index, to_index, eat_kind);
+                self.board.undo_move(&amove, eat_piece);
+                self.current_move = amove.before().unwrap();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn to_start(&mut self) {
+        while self.back() {}
+    }
+
+    // 沿主线走至末着
+    pub fn to_end(&mut self) {
+        while self.forward(0) {}
+    }
+
+    // 退至与目标着法的共同前着，再走至目标着法；目标不在本着法树时不移动
+    pub fn goto(&mut self, amove: &Arc<amove::Move>) -> bool {
+        let target_moves = amove.before_moves(true);
+        let root_move = match target_moves.first() {
+            Some(first_move) => first_move.before().unwrap(),
+            None => amove.clone(),
+        };
+        if !Arc::ptr_eq(&root_move, &self.manual_move.root_move) {
+            return false;
+        }
+
+        let current_moves = self.current_move.before_moves(true);
+        let same_count = current_moves
+            .iter()
+            .zip(&target_moves)
+            .take_while(|(amove, bmove)| Arc::ptr_eq(amove, bmove))
+            .count();
+        for _ in same_count..current_moves.len() {
+            self.back();
+        }
+        let forward_count = self.forward_moves(&target_moves[same_count..]);
+        if forward_count < target_moves.len() - same_count {
+            // 中途失败，恢复至原着法
+            for _ in 0..forward_count {
+                self.back();
+            }
+            self.forward_moves(&current_moves[same_count..]);
+            return false;
+        }
+
+        true
+    }
+
+    // 依次前进至各着法，返回成功前进的步数
+    fn forward_moves(&mut self, amoves: &[Arc<amove::Move>]) -> usize {
+        amoves
+            .iter()
+            .take_while(|amove| {
+                amove
+                    .sibling_index()
+                    .is_some_and(|(_, index)| self.forward(index))
+            })
+            .count()
+    }
+
+    // 转至当前着法的第index个同级变着
+    pub fn to_sibling(&mut self, index: usize) -> bool {
+        match self.current_move.before() {
+            Some(before) if index < before.after_len() => self.back() && self.forward(index),
+            _ => false,
+        }
+    }
+
+    pub fn to_next_sibling(&mut self) -> bool {
+        match self.current_move.sibling_index() {
+            Some((_, index)) => self.to_sibling(index + 1),
+            None => false,
+        }
+    }
+
+    pub fn to_prev_sibling(&mut self) -> bool {
+        match self.current_move.sibling_index() {
+            Some((_, index)) if index > 0 => self.to_sibling(index - 1),
+            _ => false,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -352,4 +856,140 @@ mod tests {
 
         assert_eq!("\n", manual_move.to_string(coord::RecordType::Txt));
     }
+
+    #[test]
+    fn test_read_error() {
+        let manual_move = ManualMove::from_rowcols(board::FEN, "77477062").unwrap();
+        let bytes = manual_move.get_bytes();
+        let mut pos = 0;
+        assert_eq!(
+            ManualMove::from_bin(board::FEN, &bytes, &mut pos).unwrap(),
+            manual_move
+        );
+
+        // 第二着截断
+        let mut pos = 0;
+        let result = ManualMove::from_bin(board::FEN, &bytes[..bytes.len() - 2], &mut pos);
+        assert_eq!(
+            result,
+            Err(common::GenerateError::Truncated {
+                offset: bytes.len() - 4,
+                size: 4,
+            }
+            .at_move(bytes.len() - 12, 2))
+        );
+
+        assert_eq!(
+            ManualMove::from_rowcols(board::FEN, "7747a062"),
+            Err(common::GenerateError::StringParse.at_move(4, 2))
+        );
+
+        let result =
+            ManualMove::from_string(board::FEN, "(1)\n炮三平五\n", coord::RecordType::PgnZh);
+        assert_eq!(
+            result,
+            Err(common::GenerateError::InvalidZhMove("炮三平五".to_string()).at_move(4, 1))
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let manual_moves: Vec<ManualMove> = ["77470726", "77470122", "774707269776"]
+            .iter()
+            .map(|rowcols| ManualMove::from_rowcols(board::FEN, rowcols).unwrap())
+            .collect();
+        let first_move = manual_moves[0].root_move.after().unwrap()[0].clone();
+        first_move.set_remark(String::from("中炮布局"));
+        for manual_move in &manual_moves[1..] {
+            manual_move.root_move.after().unwrap()[0].set_remark(String::from("中炮"));
+        }
+
+        let (mut manual_move, count) = ManualMove::merge_all(&manual_moves).unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(manual_move.root_move.count(), 3);
+        let amove = manual_move.root_move.after().unwrap()[0].clone();
+        assert_eq!(
+            (amove.count(), amove.remark()),
+            (3, "中炮布局\n中炮".to_string())
+        );
+        let counts: Vec<(String, usize)> = amove
+            .get_all_after_moves()
+            .iter()
+            .map(|amove| {
+                (
+                    amove.coordpair.to_string(coord::RecordType::PgnRc),
+                    amove.count(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("0726".to_string(), 2),
+                ("0122".to_string(), 1),
+                ("9776".to_string(), 1)
+            ]
+        );
+        // 原棋谱不受影响
+        assert_eq!(first_move.count(), 1);
+
+        let other_fen = "4k4/9/9/9/9/9/9/9/9/4K4";
+        assert!(!manual_move.merge(&ManualMove::new_fen(other_fen)));
+        assert!(manual_move.merge(&manual_moves[1]));
+        assert_eq!(
+            manual_move.root_move.after().unwrap()[0].after().unwrap()[1].count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_stats_diff() {
+        let manual_move = ManualMove::from_rowcols(board::FEN, "774707269776").unwrap();
+        let other = ManualMove::from_rowcols(board::FEN, "774701229776").unwrap();
+        assert_eq!(
+            manual_move.stats(),
+            MoveStats {
+                move_count: 3,
+                max_depth: 3,
+                variation_count: 0,
+                remark_count: 0,
+                leaf_count: 1,
+            }
+        );
+        assert!(manual_move.diff(&manual_move).is_same());
+
+        let diff = manual_move.diff(&other);
+        assert_eq!(diff.first_divergent_ply, Some(2));
+        assert_eq!(diff.only_self, vec!["77470726".to_string()]);
+        assert_eq!(diff.only_other, vec!["77470122".to_string()]);
+
+        let (merged, _) = ManualMove::merge_all([&manual_move, &other]).unwrap();
+        let first_move = merged.root_move.after().unwrap()[0].clone();
+        first_move.set_remark(String::from("中炮"));
+        let stats = merged.stats();
+        assert_eq!(
+            (
+                stats.move_count,
+                stats.variation_count,
+                stats.remark_count,
+                stats.leaf_count
+            ),
+            (5, 1, 1, 2)
+        );
+
+        let diff = merged.diff(&manual_move);
+        assert_eq!(diff.first_divergent_ply, None);
+        assert_eq!(diff.only_self, vec!["77470122".to_string()]);
+        assert!(diff.only_other.is_empty());
+        assert_eq!(
+            diff.remark_changes,
+            vec![("7747".to_string(), "中炮".to_string(), String::new())]
+        );
+        assert_eq!(
+            manual_move
+                .diff(&ManualMove::new_fen("4k4/9/9/9/9/9/9/9/9/4K4"))
+                .first_divergent_ply,
+            Some(0)
+        );
+    }
 }
@@ -1,14 +1,16 @@
 #![allow(dead_code)]
 
 use crate::board;
+use crate::position::{FenError, Position};
 // use diesel;
-use crate::schema::{self, manual}; //, history  aspect, evaluation,, zorbist
+use crate::schema::{self, aspect, evaluation, manual, zorbist, zorbist_state}; //, history
 use diesel::connection::SimpleConnection;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::result::Error;
 use diesel::sqlite::SqliteConnection;
 use dotenvy::dotenv;
+use serde_derive::{Deserialize, Serialize};
 use std::env;
 
 const DB_THREADS: usize = 3;
@@ -31,36 +33,39 @@ pub type SqlitePooledConnection = PooledConnection<ConnectionManager<SqliteConne
 //     pub count: i32,
 // }
 
-// #[derive(Insertable, Queryable, Selectable)]
-// #[diesel(table_name = zorbist)]
-// #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-// pub struct ZorbistData {
-//     pub id: i64,
-//     pub lock: i64,
-// }
+// (key, lock, from_index, to_index, count)
+pub type ZorbistRow = (i64, i64, i32, i32, i32);
 
-// #[derive(Insertable, Queryable, Selectable, Associations)]
-// #[diesel(belongs_to(ZorbistData, foreign_key = zorbist_id))]
-// #[diesel(table_name = aspect)]
-// #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-// pub struct AspectData {
-//     pub id: i32,
-//     pub from_index: i32,
-//     pub zorbist_id: i64,
-// }
+#[derive(Insertable, Queryable, Selectable)]
+#[diesel(table_name = zorbist)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ZorbistData {
+    pub id: i64,
+    pub lock: i64,
+}
 
-// #[derive(Insertable, Queryable, Selectable, Associations)]
-// #[diesel(belongs_to(AspectData, foreign_key = aspect_id))]
-// #[diesel(table_name = evaluation)]
-// #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-// pub struct EvaluationData {
-//     // pub id: i32,
-//     pub to_index: i32,
-//     pub count: i32,
-//     pub aspect_id: i32,
-// }
+#[derive(Insertable, Queryable, Selectable, Associations)]
+#[diesel(belongs_to(ZorbistData, foreign_key = zorbist_id))]
+#[diesel(table_name = aspect)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AspectData {
+    // pub id: i32,
+    pub from_index: i32,
+    pub zorbist_id: i64,
+}
+
+#[derive(Insertable, Queryable, Selectable, Associations)]
+#[diesel(belongs_to(AspectData, foreign_key = aspect_id))]
+#[diesel(table_name = evaluation)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct EvaluationData {
+    // pub id: i32,
+    pub to_index: i32,
+    pub count: i32,
+    pub aspect_id: i32,
+}
 
-#[derive(Insertable, Queryable, Selectable, Debug)]
+#[derive(Insertable, Queryable, Selectable, Debug, Deserialize, Serialize)]
 #[diesel(table_name = manual)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct ManualInfo {
@@ -177,41 +182,136 @@ fn set_seq_zero(conn: &mut SqliteConnection, table: &str) {
 //     }
 // }
 
-// impl ZorbistData {
-//     pub fn count(conn: &mut SqliteConnection) -> Result<i64, Error> {
-//         use diesel::dsl::count;
-//         use schema::zorbist::dsl::*;
-//         zorbist.select(count(id)).first::<i64>(conn)
-//     }
-// }
+impl ZorbistData {
+    pub fn clear(conn: &mut SqliteConnection) {
+        let _ = diesel::delete(zorbist_state::table).execute(conn);
+        let _ = diesel::delete(zorbist::table).execute(conn);
+        set_seq_zero(conn, "aspect");
+        set_seq_zero(conn, "evaluation");
+    }
 
-// impl AspectData {
-//     pub fn max_id(conn: &mut SqliteConnection) -> Result<i32, Error> {
-//         use diesel::dsl::max;
-//         use schema::aspect::dsl::*;
-//         let max_id = aspect.select(max(id)).limit(1).load::<Option<i32>>(conn)?;
-//         Ok(max_id[0].unwrap())
-//     }
+    pub fn count(conn: &mut SqliteConnection) -> Result<i64, Error> {
+        use diesel::dsl::count;
+        use schema::zorbist::dsl::*;
+        zorbist.select(count(id)).first::<i64>(conn)
+    }
 
-//     pub fn count(conn: &mut SqliteConnection) -> Result<i64, Error> {
-//         use diesel::dsl::count;
-//         use schema::aspect::dsl::*;
-//         aspect.select(count(id)).first::<i64>(conn)
-//     }
-// }
+    // 沿碰撞链查找lock相符的局面，未找到则存入链上第一个空闲位置
+    pub fn get_chain_id(
+        conn: &mut SqliteConnection,
+        key: u64,
+        lock: u64,
+        collide_keys: &[u64],
+    ) -> Result<i64, Error> {
+        let mut key = key;
+        for index in 0..=collide_keys.len() {
+            match zorbist::table
+                .find(key as i64)
+                .select(zorbist::lock)
+                .first::<i64>(conn)
+                .optional()?
+            {
+                Some(old_lock) if old_lock == lock as i64 => return Ok(key as i64),
+                Some(_) => (),
+                None => {
+                    diesel::insert_into(zorbist::table)
+                        .values(ZorbistData {
+                            id: key as i64,
+                            lock: lock as i64,
+                        })
+                        .execute(conn)?;
+                    return Ok(key as i64);
+                }
+            }
 
-// impl EvaluationData {
-//     pub fn clear(conn: &mut SqliteConnection) {
-//         let _ = diesel::delete(zorbist::table).execute(conn);
-//         set_seq_zero(conn, "evaluation");
-//     }
+            if index < collide_keys.len() {
+                key ^= collide_keys[index];
+            }
+        }
 
-//     pub fn count(conn: &mut SqliteConnection) -> Result<i64, Error> {
-//         use diesel::dsl::count;
-//         use schema::evaluation::dsl::*;
-//         evaluation.select(count(id)).first::<i64>(conn)
-//     }
-// }
+        Err(Error::NotFound)
+    }
+
+    // 已计入的manual最大id
+    pub fn get_manual_id(conn: &mut SqliteConnection) -> Result<i32, Error> {
+        Ok(zorbist_state::table
+            .find(0)
+            .select(zorbist_state::manual_id)
+            .first::<i32>(conn)
+            .optional()?
+            .unwrap_or(0))
+    }
+
+    pub fn set_manual_id(conn: &mut SqliteConnection, manual_id: i32) -> Result<usize, Error> {
+        diesel::replace_into(zorbist_state::table)
+            .values((
+                zorbist_state::id.eq(0),
+                zorbist_state::manual_id.eq(manual_id),
+            ))
+            .execute(conn)
+    }
+
+    pub fn load_all(conn: &mut SqliteConnection) -> Result<Vec<ZorbistRow>, Error> {
+        evaluation::table
+            .inner_join(aspect::table.inner_join(zorbist::table))
+            .select((
+                zorbist::id,
+                zorbist::lock,
+                aspect::from_index,
+                evaluation::to_index,
+                evaluation::count,
+            ))
+            .load(conn)
+    }
+}
+
+impl AspectData {
+    pub fn count(conn: &mut SqliteConnection) -> Result<i64, Error> {
+        use diesel::dsl::count;
+        use schema::aspect::dsl::*;
+        aspect.select(count(id)).first::<i64>(conn)
+    }
+
+    pub fn get_id(
+        conn: &mut SqliteConnection,
+        zorbist_id: i64,
+        from_index: i32,
+    ) -> Result<i32, Error> {
+        diesel::insert_into(aspect::table)
+            .values(AspectData {
+                from_index,
+                zorbist_id,
+            })
+            .on_conflict((aspect::zorbist_id, aspect::from_index))
+            .do_nothing()
+            .execute(conn)?;
+
+        aspect::table
+            .filter(aspect::zorbist_id.eq(zorbist_id))
+            .filter(aspect::from_index.eq(from_index))
+            .select(aspect::id)
+            .first::<i32>(conn)
+    }
+}
+
+impl EvaluationData {
+    pub fn count(conn: &mut SqliteConnection) -> Result<i64, Error> {
+        use diesel::dsl::count;
+        use schema::evaluation::dsl::*;
+        evaluation.select(count(id)).first::<i64>(conn)
+    }
+
+    // 已有记录则累加次数
+    pub fn save_db(&self, conn: &mut SqliteConnection) -> Result<usize, Error> {
+        use diesel::upsert::excluded;
+        diesel::insert_into(evaluation::table)
+            .values(self)
+            .on_conflict((evaluation::aspect_id, evaluation::to_index))
+            .do_update()
+            .set(evaluation::count.eq(evaluation::count + excluded(evaluation::count)))
+            .execute(conn)
+    }
+}
 
 impl ManualInfo {
     pub fn new() -> Self {
@@ -337,6 +437,32 @@ impl ManualInfo {
         board::FEN
     }
 
+    // 完整解析fen字段，缺省为开局局面
+    pub fn get_position(&self) -> Result<Position, FenError> {
+        match &self.fen {
+            Some(fen) => Position::from(fen),
+            None => Ok(Position::new()),
+        }
+    }
+
+    // fen字段中的无吃子半回合数
+    pub fn get_halfmove(&self) -> usize {
+        self.get_position()
+            .map_or(0, |position| position.halfmove())
+    }
+
+    // id大于manual_id的(id, rowcols)
+    pub fn get_id_rowcols(
+        conn: &mut SqliteConnection,
+        manual_id: i32,
+    ) -> Result<Vec<(i32, Option<String>)>, Error> {
+        manual::table
+            .filter(manual::id.gt(manual_id))
+            .order(manual::id)
+            .select((manual::id, manual::rowcols))
+            .load(conn)
+    }
+
     pub fn get_rowcols(conn: &mut SqliteConnection) -> Result<Vec<Option<String>>, Error> {
         // use diesel::dsl::max;
         use schema::manual::dsl::*;
@@ -0,0 +1,430 @@
+#![allow(dead_code)]
+
+use crate::amove;
+use crate::board;
+use crate::common;
+use crate::coord::{CoordPair, RecordType};
+use crate::models::ManualInfo;
+use crate::piece;
+use crate::position::Position;
+use crate::ucci;
+use std::sync::Arc;
+
+// 标签名与ManualInfo字段名的对应
+const TAGKEYS: [(&str, &str); 14] = [
+    ("Event", "game"),
+    ("Site", "site"),
+    ("Date", "date"),
+    ("Red", "red"),
+    ("Black", "black"),
+    ("Result", "win"),
+    ("Title", "title"),
+    ("Opening", "opening"),
+    ("Variation", "ecconame"),
+    ("ECCO", "eccosn"),
+    ("Annotator", "writer"),
+    ("Author", "author"),
+    ("Type", "atype"),
+    ("FEN", "fen"),
+];
+
+// 结果标记与ManualInfo.win的对应
+const RESULTS: [(&str, &str); 4] = [
+    ("*", "未知"),
+    ("1-0", "红胜"),
+    ("0-1", "黑胜"),
+    ("1/2-1/2", "和棋"),
+];
+
+// Format标签值与着法记法的对应，未指定时为中文记法
+const FORMATS: [(&str, RecordType); 3] = [
+    ("ICCS", RecordType::PgnIccs),
+    ("Chinese", RecordType::PgnZh),
+    ("WXF", RecordType::PgnWxf),
+];
+
+const LINEWIDTH: usize = 80;
+
+// PGN文本中的一局：标签对及着法文本
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub movetext: String,
+}
+
+impl PgnGame {
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_notation(&self) -> RecordType {
+        self.get_tag("Format")
+            .and_then(|format| FORMATS.iter().find(|&&(name, _)| name == format))
+            .map_or(RecordType::PgnZh, |&(_, notation)| notation)
+    }
+
+    // 转换为ManualInfo的键值对，Result标签转换为中文结果
+    pub fn get_key_values(&self) -> Vec<(String, String)> {
+        let mut result = vec![];
+        for (tag, value) in &self.tags {
+            if let Some(&(_, key)) = TAGKEYS.iter().find(|(name, _)| name == tag) {
+                let value = if key == "win" {
+                    get_win(value).unwrap_or(RESULTS[0].1)
+                } else {
+                    value
+                };
+                result.push((key.to_string(), value.to_string()));
+            }
+        }
+
+        result
+    }
+}
+
+fn get_win(result_str: &str) -> Option<&'static str> {
+    RESULTS
+        .iter()
+        .find(|&&(token, _)| token == result_str)
+        .map(|&(_, win)| win)
+}
+
+// 按标签行切分多局
+pub fn split_games(pgn: &str) -> Vec<PgnGame> {
+    let tag_re = regex::Regex::new(r#"^\s*\[(\w+)\s+"((?:[^"\\]|\\.)*)"\]\s*$"#).unwrap();
+    let mut games: Vec<PgnGame> = vec![];
+    let mut game = PgnGame::default();
+    for line in pgn.lines() {
+        if let Some(caps) = tag_re.captures(line) {
+            if !game.movetext.trim().is_empty() {
+                games.push(std::mem::take(&mut game));
+            }
+
+            let value = caps
+                .at(2)
+                .unwrap()
+                .replace("\\\"", "\"")
+                .replace("\\\\", "\\");
+            game.tags.push((caps.at(1).unwrap().to_string(), value));
+        } else {
+            game.movetext.push_str(line);
+            game.movetext.push('\n');
+        }
+    }
+    if !game.tags.is_empty() || !game.movetext.trim().is_empty() {
+        games.push(game);
+    }
+
+    games
+}
+
+fn get_coordpair(
+    board: &board::Board,
+    move_str: &str,
+    notation: RecordType,
+    color: piece::Color,
+) -> common::Result<CoordPair> {
+    match notation {
+        RecordType::PgnZh => board.get_coordpair_from_zhstr(move_str, color),
+        RecordType::PgnWxf => board.get_coordpair_from_wxfstr(move_str, color),
+        _ => ucci::coordpair_from_iccs(&move_str.replace('-', "").to_ascii_lowercase()),
+    }
+}
+
+fn get_move_str(board: &board::Board, coordpair: &CoordPair, notation: RecordType) -> String {
+    match notation {
+        RecordType::PgnZh => board.get_zhstr_from_coordpair(coordpair),
+        RecordType::PgnWxf => board.get_wxfstr_from_coordpair(coordpair),
+        _ => {
+            let iccs = ucci::coordpair_to_iccs(coordpair).to_ascii_uppercase();
+            format!("{}-{}", &iccs[..2], &iccs[2..])
+        }
+    }
+}
+
+// 解析着法文本至root_move之后，返回文本中的结果标记；first_color为首着的走棋方
+pub fn read_moves(
+    board: &board::Board,
+    root_move: &Arc<amove::Move>,
+    movetext: &str,
+    notation: RecordType,
+    first_color: piece::Color,
+) -> common::Result<Option<&'static str>> {
+    let token_re =
+        regex::Regex::new(r"\{([^}]*)\}|;[^\n]*|\(|\)|\$\d+|\d+\.+|[^\s{}();]+").unwrap();
+    let mut amove = root_move.clone();
+    let mut variation_moves = vec![];
+    let mut number = 0;
+    let mut win = None;
+    for caps in token_re.captures_iter(movetext) {
+        let token = caps.at(0).unwrap();
+        let offset = caps.pos(0).unwrap().0;
+        if let Some(remark) = caps.at(1) {
+            // 多个注释依次接续
+            let remark = match amove.remark() {
+                old_remark if old_remark.is_empty() => remark.trim().to_string(),
+                old_remark => format!("{old_remark}\n{}", remark.trim()),
+            };
+            amove.set_remark(remark);
+        } else if token == "(" {
+            // 变着替代前一着法
+            let before_move = amove
+                .before()
+                .ok_or(common::GenerateError::StringParse.at_move(offset, number))?;
+            variation_moves.push(amove);
+            amove = before_move;
+        } else if token == ")" {
+            amove = variation_moves
+                .pop()
+                .ok_or(common::GenerateError::StringParse.at_move(offset, number))?;
+        } else if token.starts_with(';')
+            || token.starts_with('$')
+            || token.starts_with(|ch: char| ch.is_ascii_digit()) && token.ends_with('.')
+        {
+            // 行注释、注释符号及回合数
+        } else if let Some(token_win) = get_win(token) {
+            win = Some(token_win);
+        } else {
+            number += 1;
+            let the_board = board.to_move(&amove, true);
+            let color = amove.after_color(first_color);
+            let coordpair = get_coordpair(&the_board, token, notation, color)
+                .map_err(|err| err.at_move(offset, number))?;
+            let (from_index, to_index) = coordpair.from_to_index();
+            // 须为走棋方的合法着法，以免同一方连走两着
+            if !the_board
+                .bit_board()
+                .legal_from_to_indexs(color)
+                .contains(&(from_index, to_index))
+            {
+                return Err(common::GenerateError::IllegalMove {
+                    from_index,
+                    to_index,
+                }
+                .at_move(offset, number));
+            }
+
+            amove = amove.append(coordpair, String::new());
+        }
+    }
+
+    if variation_moves.is_empty() {
+        Ok(win)
+    } else {
+        Err(common::GenerateError::StringParse.at_move(movetext.len(), number))
+    }
+}
+
+struct MoveWriter<'a> {
+    board: &'a board::Board,
+    notation: RecordType,
+    is_black_first: bool,
+    fullmove: usize,
+    tokens: Vec<String>,
+}
+
+impl MoveWriter<'_> {
+    fn push_remark(&mut self, amove: &Arc<amove::Move>) {
+        let remark = amove.remark();
+        if !remark.is_empty() {
+            self.tokens.push(format!("{{{remark}}}"));
+        }
+    }
+
+    // ply自1起算；黑方着法仅在变着开始或被注释、变着隔断后标注回合数
+    fn push_move(&mut self, amove: &Arc<amove::Move>, ply: usize, is_forced: bool) {
+        let is_red = (ply % 2 == 1) != self.is_black_first;
+        let number = self.fullmove + (ply - 1 + self.is_black_first as usize) / 2;
+        let move_str = get_move_str(
+            &self.board.to_move(amove, false),
+            &amove.coordpair,
+            self.notation,
+        );
+        self.tokens.push(match (is_red, is_forced) {
+            (true, _) => format!("{number}. {move_str}"),
+            (false, true) => format!("{number}... {move_str}"),
+            (false, false) => move_str,
+        });
+        self.push_remark(amove);
+    }
+
+    fn push_after(&mut self, amove: &Arc<amove::Move>, ply: usize, is_forced: bool) {
+        if let Some(after) = amove.after() {
+            let main_move = &after[0];
+            self.push_move(main_move, ply + 1, is_forced);
+            for other_move in &after[1..] {
+                let start = self.tokens.len();
+                self.push_move(other_move, ply + 1, true);
+                self.push_after(other_move, ply + 1, false);
+                self.tokens[start].insert(0, '(');
+                self.tokens.last_mut().unwrap().push(')');
+            }
+
+            let is_forced = after.len() > 1 || !main_move.remark().is_empty();
+            self.push_after(main_move, ply + 1, is_forced);
+        }
+    }
+}
+
+// 输出着法文本，每行不超过LINEWIDTH(单个记号除外)
+pub fn write_moves(
+    board: &board::Board,
+    root_move: &Arc<amove::Move>,
+    notation: RecordType,
+    position: &Position,
+    win: Option<&str>,
+) -> String {
+    let mut writer = MoveWriter {
+        board,
+        notation,
+        is_black_first: position.side_color() == piece::Color::Black,
+        fullmove: position.fullmove(),
+        tokens: vec![],
+    };
+    writer.push_remark(root_move);
+    writer.push_after(root_move, 0, true);
+    writer.tokens.push(get_result(win).to_string());
+
+    let mut result = String::new();
+    let mut line_len = 0;
+    for token in writer.tokens {
+        let token_len = token.chars().count();
+        if line_len > 0 && line_len + 1 + token_len > LINEWIDTH {
+            result.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            result.push(' ');
+            line_len += 1;
+        }
+
+        result.push_str(&token);
+        line_len += token_len;
+    }
+    result.push('\n');
+
+    result
+}
+
+fn get_result(win: Option<&str>) -> &'static str {
+    RESULTS
+        .iter()
+        .find(|&&(_, win_str)| Some(win_str) == win)
+        .map_or(RESULTS[0].0, |&(token, _)| token)
+}
+
+// 输出标签对，Result及Format总是输出，FEN仅在非初始局面时输出
+pub fn write_tags(info: &ManualInfo, notation: RecordType) -> String {
+    let key_values = info.get_key_values();
+    let mut result = String::new();
+    for (tag, key) in TAGKEYS {
+        let value = match key {
+            "win" => get_result(info.win.as_deref()).to_string(),
+            "fen" => match info.get_position() {
+                Ok(position) if position != Position::new() => position.get_fen(),
+                _ => continue,
+            },
+            _ => match key_values.iter().find(|&&(name, _)| name == key) {
+                Some((_, value)) => value.to_string(),
+                None => continue,
+            },
+        };
+
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        result.push_str(&format!("[{tag} \"{value}\"]\n"));
+    }
+
+    if let Some(&(format, _)) = FORMATS
+        .iter()
+        .find(|&&(_, record_type)| record_type == notation)
+    {
+        result.push_str(&format!("[Format \"{format}\"]\n"));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pgn() {
+        let pgn = r#"[Event "测试"]
+[Red "红方"]
+[Result "1-0"]
+[Format "ICCS"]
+
+{开局} 1. H2-E2 {中炮} H9-G7 (1... H7-E7 2. H0-G2) 2. H0-G2 1-0
+
+[Event "第二局"]
+
+1. 炮二平五 马８进７ 2. 马二进三 *
+"#;
+        let games = split_games(pgn);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].get_tag("Red"), Some("红方"));
+        assert_eq!(games[0].get_notation(), RecordType::PgnIccs);
+        assert_eq!(games[1].get_notation(), RecordType::PgnZh);
+        assert!(games[0]
+            .get_key_values()
+            .contains(&("win".to_string(), "红胜".to_string())));
+
+        let board = board::Board::new();
+        let root_move = amove::Move::root();
+        let win = read_moves(
+            &board,
+            &root_move,
+            &games[0].movetext,
+            RecordType::PgnIccs,
+            piece::Color::Red,
+        );
+        assert_eq!(win, Ok(Some("红胜")));
+        assert_eq!(root_move.remark(), "开局");
+        assert_eq!(root_move.get_all_after_moves().len(), 5);
+
+        let movetext = write_moves(
+            &board,
+            &root_move,
+            RecordType::PgnIccs,
+            &Position::new(),
+            win.unwrap(),
+        );
+        assert_eq!(
+            movetext,
+            "{开局} 1. H2-E2 {中炮} 1... H9-G7 (1... H7-E7 2. H0-G2) 2. H0-G2 1-0\n"
+        );
+
+        let root_move = amove::Move::root();
+        let win = read_moves(
+            &board,
+            &root_move,
+            &games[1].movetext,
+            RecordType::PgnZh,
+            piece::Color::Red,
+        );
+        assert_eq!(win, Ok(Some("未知")));
+        assert_eq!(root_move.get_all_after_moves().len(), 3);
+
+        for (movetext, offset, number) in [
+            ("1. H2-E2 H9-G7 2. H0-H1", 18, 3),
+            ("1. H2-E2 (", 10, 1),
+            // 红方连走两着
+            ("1. H2-E2 H0-G2", 9, 2),
+        ] {
+            let root_move = amove::Move::root();
+            let result = read_moves(
+                &board,
+                &root_move,
+                movetext,
+                RecordType::PgnIccs,
+                piece::Color::Red,
+            );
+            assert!(
+                matches!(result, Err(common::GenerateError::Move { offset: o, number: n, .. }) if (o, n) == (offset, number)),
+                "movetext: {movetext}"
+            );
+        }
+    }
+}
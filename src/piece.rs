@@ -50,6 +50,17 @@ pub const NAMECHARS: [[char; KINDCOUNT]; COLORCOUNT] = [
 const BLACKKNIGHTNAME: char = '馬';
 const BLACKROOKNAME: char = '車';
 const BLACKCANNONNAME: char = '砲';
+// 繁体字及异体字的棋子名称与NAMECHARS的对应
+pub const NAMEVARIANTS: [(char, char); 8] = [
+    ('帥', '帅'),
+    ('將', '将'),
+    ('車', '车'),
+    ('俥', '车'),
+    ('馬', '马'),
+    ('傌', '马'),
+    ('砲', '炮'),
+    ('包', '炮'),
+];
 
 pub const COLORARRAY: [Color; COLORCOUNT] = [Color::Red, Color::Black];
 pub const KINDARRAY: [Kind; KINDCOUNT] = [
@@ -95,7 +106,15 @@ pub fn color_from_name(name: char) -> Color {
     }
 }
 
+pub fn normalize_name(name: char) -> char {
+    NAMEVARIANTS
+        .iter()
+        .find(|&&(variant, _)| variant == name)
+        .map_or(name, |&(_, aname)| aname)
+}
+
 pub fn kind_from_name(name: char) -> Kind {
+    let name = normalize_name(name);
     for (index, aname) in NAMECHARS[color_from_name(name) as usize].iter().enumerate() {
         if name == *aname {
             return Kind::try_from_primitive(index).unwrap_or(Kind::NoKind);
@@ -0,0 +1,299 @@
+#![allow(dead_code)]
+
+use crate::bit_board::BitBoard;
+use crate::board::{self, Board, BoardProblem, Pieces};
+use crate::coord::{self, COLCOUNT, ROWCOUNT};
+use crate::piece::{self, Color, Kind};
+use std::{error, fmt};
+
+const FENFIELDCOUNT: usize = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    // 字段数为0或超过6
+    FieldCount(usize),
+    RowCount(usize),
+    // 行号自顶部起算
+    RowLength {
+        row: usize,
+        len: usize,
+    },
+    InvalidChar {
+        row: usize,
+        ch: char,
+    },
+    InvalidSide(String),
+    InvalidNumber {
+        field: &'static str,
+        value: String,
+    },
+    PieceCount {
+        color: Color,
+        kind: Kind,
+        count: usize,
+    },
+    MissingKing(Color),
+    InvalidPlace {
+        color: Color,
+        kind: Kind,
+        index: usize,
+    },
+}
+
+// 完整的六段FEN：棋子布局、走棋方、(两个占位符)、无吃子半回合数、回合数
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pieces: Pieces,
+    side_color: Color,
+    halfmove: usize,
+    fullmove: usize,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::FieldCount(count) => write!(f, "fen has {count} fields."),
+            FenError::RowCount(count) => write!(f, "fen has {count} rows."),
+            FenError::RowLength { row, len } => write!(f, "fen row {row} has {len} seats."),
+            FenError::InvalidChar { row, ch } => {
+                write!(f, "fen row {row} has invalid char '{ch}'.")
+            }
+            FenError::InvalidSide(side) => write!(f, "invalid side to move: {side}."),
+            FenError::InvalidNumber { field, value } => write!(f, "invalid {field}: {value}."),
+            FenError::PieceCount { color, kind, count } => {
+                write!(f, "{color:?} has {count} {kind:?}.")
+            }
+            FenError::MissingKing(color) => write!(f, "{color:?} king is missing."),
+            FenError::InvalidPlace { color, kind, index } => {
+                let coord = coord::Coord::from_index(*index).unwrap();
+                write!(f, "{color:?} {kind:?} can not be at {coord}.")
+            }
+        }
+    }
+}
+
+impl error::Error for FenError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+fn parse_pieces(board_fen: &str) -> Result<Pieces, FenError> {
+    let rows: Vec<&str> = board_fen.split('/').collect();
+    if rows.len() != ROWCOUNT {
+        return Err(FenError::RowCount(rows.len()));
+    }
+
+    let mut pieces = [piece::Piece::None; coord::SEATCOUNT];
+    for (row, row_str) in rows.iter().enumerate() {
+        let mut col = 0;
+        for ch in row_str.chars() {
+            if let Some(num) = ch.to_digit(10).filter(|&num| num > 0) {
+                col += num as usize;
+            } else if piece::kind(ch) != Kind::NoKind {
+                if col < COLCOUNT {
+                    pieces[row * COLCOUNT + col] = piece::Piece::new(ch);
+                }
+                col += 1;
+            } else {
+                return Err(FenError::InvalidChar { row, ch });
+            }
+        }
+
+        if col != COLCOUNT {
+            return Err(FenError::RowLength { row, len: col });
+        }
+    }
+
+    Ok(pieces)
+}
+
+fn parse_number(
+    field: &'static str,
+    value: Option<&str>,
+    default: usize,
+) -> Result<usize, FenError> {
+    match value {
+        None | Some("-") => Ok(default),
+        Some(value) => value.parse().map_err(|_| FenError::InvalidNumber {
+            field,
+            value: value.to_string(),
+        }),
+    }
+}
+
+// 检验棋子数量及位置，返回首个问题
+fn check_pieces(pieces: &Pieces) -> Result<(), FenError> {
+    match Board::from_pieces(*pieces).validate_pieces().first() {
+        None => Ok(()),
+        Some(&BoardProblem::KingCount(color, 0)) => Err(FenError::MissingKing(color)),
+        Some(&BoardProblem::KingCount(color, count)) => Err(FenError::PieceCount {
+            color,
+            kind: Kind::King,
+            count,
+        }),
+        Some(&BoardProblem::PieceCount(color, kind, count)) => {
+            Err(FenError::PieceCount { color, kind, count })
+        }
+        Some(&BoardProblem::InvalidPlace(color, kind, index)) => {
+            Err(FenError::InvalidPlace { color, kind, index })
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Position {
+    pub fn new() -> Self {
+        Position {
+            pieces: board::fen_to_pieces(board::FEN),
+            side_color: Color::Red,
+            halfmove: 0,
+            fullmove: 1,
+        }
+    }
+
+    // 缺少的字段取默认值：红方走棋，半回合数0，回合数1
+    pub fn from(fen: &str) -> Result<Self, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.is_empty() || fields.len() > FENFIELDCOUNT {
+            return Err(FenError::FieldCount(fields.len()));
+        }
+
+        let pieces = parse_pieces(fields[0])?;
+        check_pieces(&pieces)?;
+        let side_color = match fields.get(1) {
+            None | Some(&"w") | Some(&"r") => Color::Red,
+            Some(&"b") => Color::Black,
+            Some(side) => return Err(FenError::InvalidSide(side.to_string())),
+        };
+
+        Ok(Position {
+            pieces,
+            side_color,
+            halfmove: parse_number("halfmove", fields.get(4).copied(), 0)?,
+            fullmove: parse_number("fullmove", fields.get(5).copied(), 1)?,
+        })
+    }
+
+    pub fn get_fen(&self) -> String {
+        format!(
+            "{} {} - - {} {}",
+            self.board().get_fen(),
+            match self.side_color {
+                Color::Red => 'w',
+                Color::Black => 'b',
+            },
+            self.halfmove,
+            self.fullmove
+        )
+    }
+
+    pub fn board(&self) -> Board {
+        Board::from_pieces(self.pieces)
+    }
+
+    // 走棋方已设置的位棋盘
+    pub fn bit_board(&self) -> BitBoard {
+        let mut bit_board = BitBoard::from(&self.pieces);
+        bit_board.set_side_color(self.side_color);
+
+        bit_board
+    }
+
+    pub fn side_color(&self) -> Color {
+        self.side_color
+    }
+
+    pub fn halfmove(&self) -> usize {
+        self.halfmove
+    }
+
+    pub fn fullmove(&self) -> usize {
+        self.fullmove
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position() {
+        let position = Position::from(board::FEN).unwrap();
+        assert_eq!(position, Position::new());
+        assert_eq!(position.get_fen(), format!("{} w - - 0 1", board::FEN));
+
+        let fen = "4kab2/4a4/4b4/3N5/9/4N4/4n4/4B4/4A4/3AK1B2 b - - 12 40";
+        let position = Position::from(fen).unwrap();
+        assert_eq!(position.get_fen(), fen);
+        assert_eq!(position.side_color(), Color::Black);
+        assert_eq!((position.halfmove(), position.fullmove()), (12, 40));
+        assert_eq!(position.bit_board().side_color(), Color::Black);
+        assert_eq!(
+            Position::from(&format!("{} r", board::FEN)).unwrap(),
+            Position::new()
+        );
+
+        for (fen, error) in [
+            ("", FenError::FieldCount(0)),
+            ("4k4/9/9/9/9/9/9/9/4K4", FenError::RowCount(9)),
+            (
+                "4k4/9/9/9/9/9/9/9/9/4K5",
+                FenError::RowLength { row: 9, len: 10 },
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/4X4",
+                FenError::InvalidChar { row: 9, ch: 'X' },
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/4K4 x",
+                FenError::InvalidSide("x".to_string()),
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/4K4 w - - a",
+                FenError::InvalidNumber {
+                    field: "halfmove",
+                    value: "a".to_string(),
+                },
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/RRR1K4",
+                FenError::PieceCount {
+                    color: Color::Red,
+                    kind: Kind::Rook,
+                    count: 3,
+                },
+            ),
+            ("9/9/9/9/9/9/9/9/9/4K4", FenError::MissingKing(Color::Black)),
+            (
+                "4k4/9/9/9/9/9/9/9/9/2A1K4",
+                FenError::InvalidPlace {
+                    color: Color::Red,
+                    kind: Kind::Advisor,
+                    index: 83,
+                },
+            ),
+        ] {
+            assert_eq!(Position::from(fen), Err(error), "fen: {fen}");
+        }
+
+        // 黑方在底部
+        let fen = board::fen_to_change(board::FEN, coord::ChangeType::Rotate);
+        assert!(Position::from(&fen).is_ok());
+        let fen = "P3K4/9/9/9/9/9/9/9/9/3k5";
+        assert_eq!(
+            Position::from(fen),
+            Err(FenError::InvalidPlace {
+                color: Color::Red,
+                kind: Kind::Pawn,
+                index: 0,
+            })
+        );
+    }
+}
@@ -0,0 +1,406 @@
+#![allow(dead_code)]
+
+use crate::bit_board::BitBoard;
+use crate::coord::{self, COLCOUNT};
+use crate::piece::{self, Color, Kind};
+
+// 同一局面出现的次数达到此数时裁决
+pub const REPETITIONCOUNT: usize = 3;
+
+// 无吃子着法达到此半回合数(60回合)时判和
+pub const NATURALPLYLIMIT: usize = 120;
+
+// 着法性质
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveClass {
+    Check,
+    Chase,
+    Idle,
+}
+
+// 循环局面的裁决
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ruling {
+    Draw,
+    // 长将或长捉的一方判负
+    Lose(Color),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HistoryMove {
+    from_index: usize,
+    to_index: usize,
+    eat_kind: Kind,
+    color: Color,
+    class: MoveClass,
+}
+
+// 对局记录：保存各局面的键值及各着法的性质，用于判断重复局面
+pub struct GameHistory {
+    bit_board: BitBoard,
+    // 初始局面及每步之后的局面(key, lock)，均含走棋方
+    key_locks: Vec<(u64, u64)>,
+    // 与key_locks对应的无吃子半回合数
+    halfmoves: Vec<usize>,
+    moves: Vec<HistoryMove>,
+}
+
+fn get_key_lock(bit_board: &BitBoard) -> (u64, u64) {
+    let color = bit_board.side_color();
+    (bit_board.get_key(color), bit_board.get_lock(color))
+}
+
+fn is_crossed_pawn(bit_board: &BitBoard, index: usize) -> bool {
+    let Some(color) = bit_board.get_color(index) else {
+        return false;
+    };
+    let row = index / COLCOUNT;
+    if color == bit_board.bottom_color() {
+        row < coord::ROWCOUNT / 2
+    } else {
+        row >= coord::ROWCOUNT / 2
+    }
+}
+
+// 已到对方底线的兵卒，只能横走，不再有进攻能力
+fn is_bottom_pawn(bit_board: &BitBoard, index: usize) -> bool {
+    let Some(color) = bit_board.get_color(index) else {
+        return false;
+    };
+    let row = index / COLCOUNT;
+    if color == bit_board.bottom_color() {
+        row == 0
+    } else {
+        row == coord::ROWCOUNT - 1
+    }
+}
+
+// 对方(color的对方)能否合法地走到index吃回
+fn is_protected(bit_board: &mut BitBoard, color: Color, index: usize) -> bool {
+    let other_color = piece::other_color(color);
+    bit_board
+        .get_capture_from_to_indexs(other_color)
+        .into_iter()
+        .any(|(from_index, to_index)| {
+            to_index == index && bit_board.is_legal_move(other_color, from_index, to_index)
+        })
+}
+
+// color一方可构成"捉"的目标：可合法吃去，且为无根子，或以马炮攻车；将帅、兵卒的攻击及未过河兵卒除外
+fn get_chase_targets(bit_board: &mut BitBoard, color: Color) -> Vec<usize> {
+    let mut result = vec![];
+    for (from_index, to_index) in bit_board.get_capture_from_to_indexs(color) {
+        let kind = bit_board.get_kind(from_index);
+        let eat_kind = bit_board.get_kind(to_index);
+        if matches!(kind, Kind::King | Kind::Pawn)
+            || eat_kind == Kind::King
+            || (eat_kind == Kind::Pawn && !is_crossed_pawn(bit_board, to_index))
+            || result.contains(&to_index)
+            || !bit_board.is_legal_move(color, from_index, to_index)
+        {
+            continue;
+        }
+
+        let is_chase = if eat_kind == Kind::Rook && matches!(kind, Kind::Knight | Kind::Cannon) {
+            true
+        } else {
+            let eat_kind = bit_board.do_move(from_index, to_index).unwrap();
+            let is_protected = is_protected(bit_board, color, to_index);
+            bit_board.undo_move(from_index, to_index, eat_kind);
+
+            !is_protected
+        };
+        if is_chase {
+            result.push(to_index);
+        }
+    }
+
+    result
+}
+
+// 双方均无车、马、炮及未到底线的兵卒等进攻棋子，无法将死对方
+pub fn is_insufficient_material(bit_board: &BitBoard) -> bool {
+    [Color::Red, Color::Black].iter().all(|&color| {
+        [Kind::Rook, Kind::Knight, Kind::Cannon]
+            .iter()
+            .all(|&kind| bit_board.get_indexs_from_color_kind(color, kind).is_empty())
+            && bit_board
+                .get_indexs_from_color_kind(color, Kind::Pawn)
+                .iter()
+                .all(|&index| is_bottom_pawn(bit_board, index))
+    })
+}
+
+// 循环中一方着法的违规程度：全为将军为2，将军与捉(至少一捉)为1，其他为0
+fn get_violation(classes: &[MoveClass]) -> usize {
+    if classes.is_empty() || classes.contains(&MoveClass::Idle) {
+        0
+    } else if classes.iter().all(|&class| class == MoveClass::Check) {
+        2
+    } else {
+        1
+    }
+}
+
+impl GameHistory {
+    pub fn from(bit_board: BitBoard) -> Self {
+        Self::from_halfmove(bit_board, 0)
+    }
+
+    // halfmove: 初始局面已有的无吃子半回合数(FEN第5段)
+    pub fn from_halfmove(bit_board: BitBoard, halfmove: usize) -> Self {
+        GameHistory {
+            key_locks: vec![get_key_lock(&bit_board)],
+            halfmoves: vec![halfmove],
+            bit_board,
+            moves: vec![],
+        }
+    }
+
+    pub fn bit_board(&self) -> &BitBoard {
+        &self.bit_board
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    // 走棋方走一步并记录，着法不合法时返回None
+    pub fn do_move(&mut self, from_index: usize, to_index: usize) -> Option<MoveClass> {
+        let color = self.bit_board.side_color();
+        if self.bit_board.get_color(from_index) != Some(color)
+            || !self.bit_board.is_legal_move(color, from_index, to_index)
+        {
+            return None;
+        }
+
+        let old_targets = get_chase_targets(&mut self.bit_board, color);
+        let eat_kind = self.bit_board.do_move(from_index, to_index)?;
+        let other_color = piece::other_color(color);
+        let class = if eat_kind != Kind::King && self.bit_board.is_killed(other_color) {
+            MoveClass::Check
+        } else if get_chase_targets(&mut self.bit_board, color)
+            .iter()
+            .any(|index| !old_targets.contains(index))
+        {
+            MoveClass::Chase
+        } else {
+            MoveClass::Idle
+        };
+
+        self.key_locks.push(get_key_lock(&self.bit_board));
+        self.halfmoves.push(if eat_kind == Kind::NoKind {
+            self.get_halfmove() + 1
+        } else {
+            0
+        });
+        self.moves.push(HistoryMove {
+            from_index,
+            to_index,
+            eat_kind,
+            color,
+            class,
+        });
+
+        Some(class)
+    }
+
+    pub fn undo_move(&mut self) -> bool {
+        match self.moves.pop() {
+            Some(amove) => {
+                self.key_locks.pop();
+                self.halfmoves.pop();
+                self.bit_board
+                    .undo_move(amove.from_index, amove.to_index, amove.eat_kind);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_classes(&self) -> Vec<MoveClass> {
+        self.moves.iter().map(|amove| amove.class).collect()
+    }
+
+    // 当前局面在记录中出现的次数(含当前局面)
+    pub fn repetition_count(&self) -> usize {
+        let key_lock = self.key_locks.last().unwrap();
+        self.key_locks
+            .iter()
+            .filter(|&other_key_lock| other_key_lock == key_lock)
+            .count()
+    }
+
+    // 当前局面重复达到count次时，按循环中双方着法的性质裁决
+    pub fn ruling_count(&self, count: usize) -> Option<Ruling> {
+        let key_lock = self.key_locks.last().unwrap();
+        let indexs: Vec<usize> = self
+            .key_locks
+            .iter()
+            .enumerate()
+            .filter(|&(_, other_key_lock)| other_key_lock == key_lock)
+            .map(|(index, _)| index)
+            .collect();
+        if count < 2 || indexs.len() < count {
+            return None;
+        }
+
+        // 自倒数第count次出现至今的着法构成循环
+        let cycle_moves = &self.moves[indexs[indexs.len() - count]..];
+        let get_color_classes = |color: Color| -> Vec<MoveClass> {
+            cycle_moves
+                .iter()
+                .filter(|amove| amove.color == color)
+                .map(|amove| amove.class)
+                .collect()
+        };
+        let red_violation = get_violation(&get_color_classes(Color::Red));
+        let black_violation = get_violation(&get_color_classes(Color::Black));
+
+        Some(match red_violation.cmp(&black_violation) {
+            std::cmp::Ordering::Equal => Ruling::Draw,
+            std::cmp::Ordering::Greater => Ruling::Lose(Color::Red),
+            std::cmp::Ordering::Less => Ruling::Lose(Color::Black),
+        })
+    }
+
+    pub fn ruling(&self) -> Option<Ruling> {
+        self.ruling_count(REPETITIONCOUNT)
+    }
+
+    pub fn get_halfmove(&self) -> usize {
+        *self.halfmoves.last().unwrap()
+    }
+
+    pub fn is_natural_limit(&self) -> bool {
+        self.get_halfmove() >= NATURALPLYLIMIT
+    }
+
+    // 重复局面、自然限着或子力不足时的裁决
+    pub fn adjudicate(&self) -> Option<Ruling> {
+        if let Some(ruling) = self.ruling() {
+            return Some(ruling);
+        }
+
+        if self.is_natural_limit() || is_insufficient_material(&self.bit_board) {
+            Some(Ruling::Draw)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+
+    fn get_history(fen: &str, from_to_indexs: &[(usize, usize)], times: usize) -> GameHistory {
+        let mut history = GameHistory::from(BitBoard::from(&board::fen_to_pieces(fen)));
+        for _ in 0..times {
+            for &(from_index, to_index) in from_to_indexs {
+                assert!(history.do_move(from_index, to_index).is_some());
+            }
+        }
+
+        history
+    }
+
+    #[test]
+    fn test_game_history() {
+        // 长将：车左右追将
+        let fen = "4k4/9/9/9/9/R8/9/9/9/3K5";
+        let mut history = get_history(fen, &[(45, 49)], 1);
+        let from_to_indexs = [(4, 5), (49, 50), (5, 4), (50, 49)];
+        for _ in 0..2 {
+            for &(from_index, to_index) in &from_to_indexs {
+                assert!(history.do_move(from_index, to_index).is_some());
+            }
+        }
+        assert_eq!(history.repetition_count(), 3);
+        assert_eq!(history.ruling(), Some(Ruling::Lose(Color::Red)));
+        assert_eq!(
+            history.get_classes()[..3],
+            [MoveClass::Check, MoveClass::Idle, MoveClass::Check]
+        );
+
+        history.undo_move();
+        assert_eq!(history.repetition_count(), 2);
+        assert_eq!(history.ruling(), None);
+        assert!(history.do_move(49, 49).is_none());
+
+        // 长捉：车捉无根炮
+        let fen = "4k4/9/c8/9/9/1R7/9/9/9/3K5";
+        let mut history = get_history(fen, &[(46, 45)], 1);
+        let from_to_indexs = [(18, 19), (45, 46), (19, 18), (46, 45)];
+        for _ in 0..2 {
+            for &(from_index, to_index) in &from_to_indexs {
+                assert!(history.do_move(from_index, to_index).is_some());
+            }
+        }
+        assert_eq!(
+            history.get_classes()[..3],
+            [MoveClass::Chase, MoveClass::Idle, MoveClass::Chase]
+        );
+        assert_eq!(history.ruling(), Some(Ruling::Lose(Color::Red)));
+
+        // 双方闲着：判和
+        let history = get_history(
+            "4k4/9/9/9/9/R8/9/9/9/3K5",
+            &[(45, 46), (4, 13), (46, 45), (13, 4)],
+            2,
+        );
+        assert_eq!(history.repetition_count(), 3);
+        assert_eq!(history.ruling(), Some(Ruling::Draw));
+    }
+
+    #[test]
+    fn test_natural_limit() {
+        let fen = "4k4/9/9/9/9/R8/9/9/9/3K5";
+        let bit_board = BitBoard::from(&board::fen_to_pieces(fen));
+        let mut history = GameHistory::from_halfmove(bit_board, 117);
+        for (from_index, to_index) in [(45, 46), (4, 13)] {
+            history.do_move(from_index, to_index);
+            assert!(!history.is_natural_limit());
+            assert_eq!(history.adjudicate(), None);
+        }
+        history.do_move(46, 47);
+        assert!(history.is_natural_limit());
+        assert_eq!(history.adjudicate(), Some(Ruling::Draw));
+        history.undo_move();
+        assert_eq!(history.get_halfmove(), 119);
+
+        // 吃子后重新计数
+        let fen = "4k4/9/9/9/9/R7p/9/9/9/3K5";
+        let bit_board = BitBoard::from(&board::fen_to_pieces(fen));
+        let mut history = GameHistory::from_halfmove(bit_board, 100);
+        history.do_move(45, 53);
+        assert_eq!(history.get_halfmove(), 0);
+        assert!(history
+            .bit_board()
+            .get_indexs_from_color_kind(Color::Black, Kind::Pawn)
+            .is_empty());
+
+        // 子力不足
+        for (fen, is_insufficient) in [
+            (board::FEN, false),
+            ("3ak4/4a4/4b4/9/9/9/9/4B4/4A4/3AK1B2", true),
+            ("3ak4/4a4/4bP3/9/9/9/9/4B4/4A4/3AK1B2", false),
+            // 未过河的兵卒仍可过河进攻
+            ("3ak4/4a4/4b4/9/p8/9/9/4B4/4A4/3AK1B2", false),
+            // 兵卒均已到对方底线
+            ("P2ak4/4a4/4b4/9/9/9/9/4B4/4A4/3AK1B1p", true),
+        ] {
+            let bit_board = BitBoard::from(&board::fen_to_pieces(fen));
+            assert_eq!(
+                is_insufficient_material(&bit_board),
+                is_insufficient,
+                "fen: {fen}"
+            );
+        }
+    }
+}
@@ -0,0 +1,383 @@
+#![allow(dead_code)]
+
+use crate::bit_board::BitBoard;
+use crate::common;
+use crate::coord::{self, CoordPair, ROWCOUNT};
+use crate::engine::{self, Engine, SearchLimit, SearchResult};
+use crate::piece;
+use crate::position::Position;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const ENGINENAME: &str = "chess";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Ucci,
+    // 兼容Pikafish等前端的UCI方言：FEN以w/b表示走棋方，着法为小写ICCS坐标
+    Uci,
+}
+
+// 未指定剩余步数时，每步用时为剩余时间的若干分之一
+const DEFAULTMOVESTOGO: u64 = 20;
+
+// ICCS坐标(如h2e2)：列a-i，行0-9自红方底线起算；本库PgnIccs坐标的行自顶部起算
+pub fn coordpair_from_iccs(iccs: &str) -> common::Result<CoordPair> {
+    if iccs.len() != 4 || !iccs.is_ascii() {
+        return Err(common::GenerateError::StringParse);
+    }
+
+    let mut pgniccs = String::new();
+    for (index, ch) in iccs.chars().enumerate() {
+        if index % 2 == 0 {
+            pgniccs.push(ch.to_ascii_uppercase());
+        } else {
+            let row = ch.to_digit(10).ok_or(common::GenerateError::StringParse)? as usize;
+            pgniccs.push_str(&(ROWCOUNT - 1 - row).to_string());
+        }
+    }
+
+    CoordPair::from_string(&pgniccs, coord::RecordType::PgnIccs)
+}
+
+pub fn coordpair_to_iccs(coordpair: &CoordPair) -> String {
+    let mut result = String::new();
+    for coord in [coordpair.from_coord, coordpair.to_coord] {
+        result.push((b'a' + coord.col as u8) as char);
+        result.push_str(&(ROWCOUNT - 1 - coord.row).to_string());
+    }
+
+    result
+}
+
+// UCI方言的FEN可能以h/e表示马/象，统一为本库的n/b
+pub fn fen_from_uci(fen: &str) -> String {
+    fen.chars()
+        .map(|ch| match ch {
+            'h' => 'n',
+            'H' => 'N',
+            'e' => 'b',
+            'E' => 'B',
+            _ => ch,
+        })
+        .collect()
+}
+
+fn send_line<W: Write>(output: &Mutex<W>, line: &str) {
+    if let Ok(mut output) = output.lock() {
+        let _ = writeln!(output, "{line}");
+        let _ = output.flush();
+    }
+}
+
+// UCI在杀棋范围内以回合数报告：score mate n，负数为被杀
+fn get_score(value: i32, protocol: Protocol) -> String {
+    match protocol {
+        Protocol::Ucci => format!("score {value}"),
+        Protocol::Uci if value >= engine::WINVALUE => {
+            format!("score mate {}", (engine::MATEVALUE - value + 1) / 2)
+        }
+        Protocol::Uci if value <= -engine::WINVALUE => {
+            format!("score mate -{}", (engine::MATEVALUE + value) / 2)
+        }
+        Protocol::Uci => format!("score cp {value}"),
+    }
+}
+
+fn send_result<W: Write>(output: &Mutex<W>, result: &SearchResult, protocol: Protocol) {
+    if result.depth > 0 {
+        let pv: Vec<String> = result.pv.iter().map(coordpair_to_iccs).collect();
+        send_line(
+            output,
+            &format!(
+                "info depth {} {} nodes {} pv {}",
+                result.depth,
+                get_score(result.value, protocol),
+                result.nodes,
+                pv.join(" ")
+            ),
+        );
+    }
+
+    match &result.best_move {
+        Some(best_move) => send_line(
+            output,
+            &format!("bestmove {}", coordpair_to_iccs(best_move)),
+        ),
+        None => match protocol {
+            Protocol::Ucci => send_line(output, "nobestmove"),
+            Protocol::Uci => send_line(output, "bestmove (none)"),
+        },
+    }
+}
+
+pub struct Ucci<W: Write + Send + 'static> {
+    output: Arc<Mutex<W>>,
+    protocol: Protocol,
+    bit_board: BitBoard,
+
+    // 搜索时引擎移交至搜索线程，结束后收回
+    engine: Option<Engine>,
+    stop_flag: Arc<AtomicBool>,
+    search_handle: Option<JoinHandle<Engine>>,
+}
+
+impl<W: Write + Send + 'static> Ucci<W> {
+    pub fn new(output: W) -> Self {
+        let engine = Engine::new();
+        Ucci {
+            output: Arc::new(Mutex::new(output)),
+            protocol: Protocol::Ucci,
+            bit_board: BitBoard::new(),
+
+            stop_flag: engine.get_stop_flag(),
+            engine: Some(engine),
+            search_handle: None,
+        }
+    }
+
+    fn send(&self, line: &str) {
+        send_line(&self.output, line);
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    // 执行一行指令，收到quit时返回false；首条ucci/uci指令决定所用协议
+    pub fn execute(&mut self, line: &str) -> bool {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        match args.first() {
+            Some(&"ucci") => {
+                self.protocol = Protocol::Ucci;
+                self.send(&format!("id name {ENGINENAME}"));
+                self.send(&format!("id version {}", env!("CARGO_PKG_VERSION")));
+                self.send("ucciok");
+            }
+            Some(&"uci") => {
+                self.protocol = Protocol::Uci;
+                self.send(&format!(
+                    "id name {ENGINENAME} {}",
+                    env!("CARGO_PKG_VERSION")
+                ));
+                self.send("uciok");
+            }
+            Some(&"isready") => self.send("readyok"),
+            Some(&"ucinewgame") => {
+                self.stop();
+                if let Some(engine) = self.engine.as_mut() {
+                    engine.clear();
+                }
+            }
+            Some(&"position") => {
+                self.wait();
+                if self.position(&args[1..]).is_err() {
+                    self.send(&format!("info string invalid position: {line}"));
+                }
+            }
+            Some(&"go") => self.go(&args[1..]),
+            Some(&"stop") => self.stop(),
+            Some(&"quit") => {
+                self.stop();
+                if self.protocol == Protocol::Ucci {
+                    self.send("bye");
+                }
+                return false;
+            }
+            _ => (),
+        }
+
+        true
+    }
+
+    // position {fen <fen串> | startpos} [moves <着法列表>]
+    fn position(&mut self, args: &[&str]) -> common::Result<()> {
+        let moves_index = args.iter().position(|&arg| arg == "moves");
+        let fen_args = &args[..moves_index.unwrap_or(args.len())];
+        let position = match fen_args.first() {
+            Some(&"startpos") => Position::new(),
+            Some(&"fen") if fen_args.len() > 1 => {
+                let mut fields = fen_args[1..].to_vec();
+                let board_fen = fen_from_uci(fields[0]);
+                fields[0] = &board_fen;
+                Position::from(&fields.join(" ")).map_err(|_| common::GenerateError::StringParse)?
+            }
+            _ => return Err(common::GenerateError::StringParse),
+        };
+
+        let mut bit_board = position.bit_board();
+        if let Some(moves_index) = moves_index {
+            for iccs in &args[moves_index + 1..] {
+                let (from_index, to_index) = coordpair_from_iccs(iccs)?.from_to_index();
+                let color = bit_board.side_color();
+                if !bit_board
+                    .legal_from_to_indexs(color)
+                    .contains(&(from_index, to_index))
+                {
+                    return Err(common::GenerateError::StringParse);
+                }
+
+                bit_board.do_move(from_index, to_index);
+            }
+        }
+
+        self.bit_board = bit_board;
+        Ok(())
+    }
+
+    // UCCI: go [depth <d> | time <t> [movestogo <m> | increment <i>] | infinite]
+    // UCI: go [depth <d> | movetime <t> | wtime <t> btime <t> [winc <i> binc <i>] [movestogo <m>] | infinite]
+    fn go(&mut self, args: &[&str]) {
+        let get_value = |name| {
+            let index = args.iter().position(|&arg| arg == name)?;
+            args.get(index + 1)?.parse::<u64>().ok()
+        };
+
+        let (time_name, increment_name) = match self.protocol {
+            Protocol::Ucci => ("time", "increment"),
+            Protocol::Uci => match self.bit_board.side_color() {
+                piece::Color::Red => ("wtime", "winc"),
+                _ => ("btime", "binc"),
+            },
+        };
+        let limit = if let Some(depth) = get_value("depth") {
+            SearchLimit::Depth(depth as usize)
+        } else if let Some(time) = get_value("movetime") {
+            SearchLimit::Time(Duration::from_millis(time))
+        } else if let Some(time) = get_value(time_name) {
+            let movestogo = get_value("movestogo").unwrap_or(DEFAULTMOVESTOGO).max(1);
+            let increment = get_value(increment_name).unwrap_or(0);
+            SearchLimit::Time(Duration::from_millis(time / movestogo + increment))
+        } else {
+            SearchLimit::Depth(engine::MAXDEPTH)
+        };
+
+        self.wait();
+        let mut engine = self.engine.take().unwrap_or_else(Engine::new);
+        let output = self.output.clone();
+        let bit_board = self.bit_board;
+        let protocol = self.protocol;
+        self.stop_flag = engine.get_stop_flag();
+        self.stop_flag.store(false, Ordering::Relaxed);
+        self.search_handle = Some(thread::spawn(move || {
+            let result = engine.search(&bit_board, limit);
+            send_result(&output, &result, protocol);
+
+            engine
+        }));
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        self.wait();
+    }
+
+    // 等待搜索线程结束并收回引擎
+    pub fn wait(&mut self) {
+        if let Some(search_handle) = self.search_handle.take() {
+            self.engine = Some(search_handle.join().unwrap_or_else(|_| Engine::new()));
+        }
+    }
+}
+
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) -> io::Result<()> {
+    let mut ucci = Ucci::new(output);
+    for line in input.lines() {
+        if !ucci.execute(&line?) {
+            break;
+        }
+    }
+    ucci.wait();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_ucci() {
+        let coordpair = CoordPair::from_row_col(7, 7, 7, 4).unwrap();
+        assert_eq!(coordpair_from_iccs("h2e2").unwrap(), coordpair);
+        assert_eq!(coordpair_to_iccs(&coordpair), "h2e2");
+        assert!(coordpair_from_iccs("h2e").is_err());
+
+        let buffer = SharedBuffer(Arc::new(Mutex::new(vec![])));
+        let mut ucci = Ucci::new(buffer.clone());
+        for line in [
+            "ucci",
+            "isready",
+            "position startpos moves h2e2 h9g7",
+            "position fen 3k5/8R/9/9/9/R8/9/9/9/4K4 w - - 0 1",
+            "go depth 2",
+        ] {
+            assert!(ucci.execute(line));
+        }
+        ucci.wait();
+        assert!(!ucci.execute("quit"));
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[..3], ["id name chess", "id version 0.1.0", "ucciok"]);
+        assert_eq!(lines[3], "readyok");
+        // 车九进五一步杀：深度1即得杀棋分值
+        assert!(lines[4].starts_with(&format!("info depth 1 score {} ", engine::MATEVALUE - 1)));
+        assert!(lines[4].ends_with(" pv a4a9"));
+        assert_eq!(lines[5], "bestmove a4a9");
+        assert_eq!(lines[6], "bye");
+    }
+
+    #[test]
+    fn test_uci() {
+        assert_eq!(fen_from_uci("rheakaehr/9/1c5c1"), "rnbakabnr/9/1c5c1");
+        for (value, ucci_score, uci_score) in [
+            (35, "score 35", "score cp 35"),
+            (engine::MATEVALUE - 1, "score 9999", "score mate 1"),
+            (engine::MATEVALUE - 3, "score 9997", "score mate 2"),
+            (-engine::MATEVALUE + 2, "score -9998", "score mate -1"),
+        ] {
+            assert_eq!(get_score(value, Protocol::Ucci), ucci_score);
+            assert_eq!(get_score(value, Protocol::Uci), uci_score);
+        }
+
+        let buffer = SharedBuffer(Arc::new(Mutex::new(vec![])));
+        let mut ucci = Ucci::new(buffer.clone());
+        for line in [
+            "uci",
+            "ucinewgame",
+            "isready",
+            "position fen rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1 moves h2e2",
+            "position fen 4k4/9/9/9/9/9/9/9/r8/r3K4 w - - 0 1",
+            "go movetime 100",
+        ] {
+            assert!(ucci.execute(line));
+        }
+        assert_eq!(ucci.protocol(), Protocol::Uci);
+        ucci.wait();
+        assert!(!ucci.execute("quit"));
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            ["id name chess 0.1.0", "uciok", "readyok", "bestmove (none)"]
+        );
+    }
+}